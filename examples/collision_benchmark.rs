@@ -0,0 +1,144 @@
+//! Headless benchmark scene: one hero, a wave of shooters and thousands of fireballs.
+//!
+//! Runs the collision pipeline with the default spatial grid and with a single huge cell,
+//! which degenerates into the old every-pair loop.
+//!
+//! `cargo run --release --example collision_benchmark`
+
+use std::time::{Duration, Instant};
+
+use eangine::{
+    collision::{CollisionLayerConfig, CollisionLayerName},
+    component::{Collider, CollisionLayer, Fireball, Hero, Position, Render, Shooter, Velocity},
+    enemy::{default_enemy_layer, default_projectile_layer},
+    resources::RenderLayer,
+    scene::SceneConfig,
+    spatial::SpatialGrid,
+    sprite::intern_sprite,
+    system::{CollisionSystem, FireballSystem, SpatialGridSystem, WalkSystem},
+};
+use quicksilver::geom::Vector;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use specs::{Builder, RunNow, World};
+
+const FIREBALLS: usize = 5000;
+const SHOOTERS: usize = 200;
+const TICKS: u32 = 300;
+
+fn create_world(cell_size: f32) -> World {
+    let mut world = eangine::scene::create_world(&SceneConfig::default());
+    world.add_resource(SpatialGrid::new(cell_size));
+
+    let hero_sprite = intern_sprite(&world, "heroi");
+    let shooter_sprite = intern_sprite(&world, "atirador");
+    let fireball_sprite = intern_sprite(&world, "fogo");
+
+    let mut rng = StdRng::seed_from_u64(42);
+    world
+        .create_entity()
+        .with(Hero {
+            lives: i32::max_value(),
+            score: 0,
+            blinking: false,
+            render: true,
+            reset_position: false,
+            blink_timer: Duration::from_millis(0),
+            air_jumps: 0,
+            jump_pressed: false,
+            invincible: false,
+        })
+        .with(Position {
+            position: Vector::new(400, 425),
+        })
+        .with(Render {
+            sprite: hero_sprite,
+            layer: RenderLayer::World,
+            z: 0.0,
+        })
+        .with(Collider {
+            hitboxes: eangine::collider::body(Vector::new(40, 60)),
+        })
+        .with(CollisionLayer::new(&CollisionLayerConfig::new(
+            &[CollisionLayerName::Hero],
+            &[CollisionLayerName::Projectile],
+        )))
+        .build();
+
+    for _ in 0..SHOOTERS {
+        world
+            .create_entity()
+            .with(Position {
+                position: Vector::new(rng.gen_range(0.0, 4000.0), rng.gen_range(0.0, 4000.0)),
+            })
+            .with(Render {
+                sprite: shooter_sprite,
+                layer: RenderLayer::World,
+                z: 0.0,
+            })
+            .with(Collider {
+                hitboxes: eangine::collider::body(Vector::new(50, 50)),
+            })
+            .with(CollisionLayer::new(&default_enemy_layer()))
+            .with(Shooter {
+                projectile_sprite: "tiro".to_string(),
+                projectile_collider: eangine::enemy::default_projectile_collider(),
+                projectile_layer: default_projectile_layer(),
+                projectile_render_layer: RenderLayer::Projectiles,
+                fast_projectiles: true,
+                projectile_trail: None,
+                maximum_fireballs: 2,
+                fireball_amount: 0,
+                coefficient: (0.175, 0.0),
+                active: true,
+            })
+            .build();
+    }
+
+    for _ in 0..FIREBALLS {
+        world
+            .create_entity()
+            .with(Fireball { owner_id: None })
+            .with(Position {
+                position: Vector::new(rng.gen_range(0.0, 4000.0), rng.gen_range(0.0, 4000.0)),
+            })
+            .with(Velocity {
+                velocity: Vector::new(rng.gen_range(-100.0, 100.0), rng.gen_range(-100.0, 100.0)),
+            })
+            .with(Render {
+                sprite: fireball_sprite,
+                layer: RenderLayer::Projectiles,
+                z: 0.0,
+            })
+            .with(Collider {
+                hitboxes: eangine::collider::body(Vector::new(16, 16)),
+            })
+            .with(CollisionLayer::new(&default_projectile_layer()))
+            .build();
+    }
+    world
+}
+
+fn run(name: &str, cell_size: f32) {
+    let mut world = create_world(cell_size);
+    let start = Instant::now();
+    for _ in 0..TICKS {
+        WalkSystem.run_now(&world.res);
+        FireballSystem.run_now(&world.res);
+        SpatialGridSystem.run_now(&world.res);
+        CollisionSystem.run_now(&world.res);
+        world.maintain();
+    }
+    let elapsed = start.elapsed();
+    let elapsed_ms = elapsed.as_secs() as f64 * 1e3 + f64::from(elapsed.subsec_nanos()) * 1e-6;
+    println!(
+        "{:<12} {:>8.3} ms/tick ({} ticks)",
+        name,
+        elapsed_ms / f64::from(TICKS),
+        TICKS
+    );
+}
+
+fn main() {
+    run("grid", 128.0);
+    run("single cell", 1.0e9);
+}
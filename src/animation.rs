@@ -0,0 +1,174 @@
+use std::{collections::HashMap, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Hash, Copy, Clone)]
+pub enum AnimationClipName {
+    Idle,
+    Walk,
+    Jump,
+    Hurt,
+    Death,
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Copy, Clone)]
+pub enum AnimationMode {
+    Loop,
+    /// Stops on the last frame.
+    Once,
+    /// Plays forward then backward.
+    PingPong,
+}
+
+/// Sequence of atlas images. Frames past the end of `frame_durations_ms` reuse its last value.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct AnimationClip {
+    pub frames: Vec<String>,
+    pub frame_durations_ms: Vec<u64>,
+    pub mode: AnimationMode,
+}
+
+impl Default for AnimationClip {
+    fn default() -> AnimationClip {
+        AnimationClip {
+            frames: Vec::new(),
+            frame_durations_ms: vec![100],
+            mode: AnimationMode::Loop,
+        }
+    }
+}
+
+impl AnimationClip {
+    /// Clip showing a single image, e.g. the defaults built from a prefab sprite.
+    pub fn still(sprite: &str, duration_ms: u64, mode: AnimationMode) -> AnimationClip {
+        AnimationClip {
+            frames: vec![sprite.to_string()],
+            frame_durations_ms: vec![duration_ms],
+            mode,
+        }
+    }
+
+    /// Time to play every frame once.
+    pub fn duration(&self) -> Duration {
+        (0..self.frames.len())
+            .map(|frame| self.frame_duration(frame))
+            .sum()
+    }
+
+    pub fn frame_duration(&self, frame: usize) -> Duration {
+        let duration_ms = self
+            .frame_durations_ms
+            .get(frame)
+            .or_else(|| self.frame_durations_ms.last())
+            .cloned()
+            .unwrap_or(100);
+        Duration::from_millis(duration_ms)
+    }
+
+    /// Frame following `frame`, with the new playing direction, or `None` once a
+    /// `Once` clip is over.
+    pub fn next_frame(&self, frame: usize, forward: bool) -> Option<(usize, bool)> {
+        let last = self.frames.len().saturating_sub(1);
+        match self.mode {
+            AnimationMode::Loop => Some((if frame >= last { 0 } else { frame + 1 }, true)),
+            AnimationMode::Once => {
+                if frame >= last {
+                    None
+                } else {
+                    Some((frame + 1, true))
+                }
+            }
+            AnimationMode::PingPong => {
+                if last == 0 {
+                    Some((0, forward))
+                } else if forward && frame >= last {
+                    Some((last - 1, false))
+                } else if !forward && frame == 0 {
+                    Some((1, true))
+                } else if forward {
+                    Some((frame + 1, true))
+                } else {
+                    Some((frame - 1, false))
+                }
+            }
+        }
+    }
+}
+
+pub type AnimationClips = HashMap<AnimationClipName, AnimationClip>;
+
+/// Clips for prefabs configured without any: the sprite for every state, held for a short
+/// hurt flash and a death pose.
+pub fn still_clips(sprite: &str) -> AnimationClips {
+    let mut clips = AnimationClips::new();
+    clips.insert(
+        AnimationClipName::Idle,
+        AnimationClip::still(sprite, 100, AnimationMode::Loop),
+    );
+    clips.insert(
+        AnimationClipName::Hurt,
+        AnimationClip::still(sprite, 300, AnimationMode::Once),
+    );
+    clips.insert(
+        AnimationClipName::Death,
+        AnimationClip::still(sprite, 500, AnimationMode::Once),
+    );
+    clips
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clip(frames: usize, mode: AnimationMode) -> AnimationClip {
+        AnimationClip {
+            frames: (0..frames).map(|frame| frame.to_string()).collect(),
+            frame_durations_ms: vec![100, 50],
+            mode,
+        }
+    }
+
+    #[test]
+    fn loop_restarts() {
+        let clip = clip(3, AnimationMode::Loop);
+        assert_eq!(clip.next_frame(1, true), Some((2, true)));
+        assert_eq!(clip.next_frame(2, true), Some((0, true)));
+    }
+
+    #[test]
+    fn once_stops_on_the_last_frame() {
+        let clip = clip(2, AnimationMode::Once);
+        assert_eq!(clip.next_frame(0, true), Some((1, true)));
+        assert_eq!(clip.next_frame(1, true), None);
+    }
+
+    #[test]
+    fn ping_pong_turns_at_both_ends() {
+        let clip = clip(3, AnimationMode::PingPong);
+        assert_eq!(clip.next_frame(2, true), Some((1, false)));
+        assert_eq!(clip.next_frame(1, false), Some((0, false)));
+        assert_eq!(clip.next_frame(0, false), Some((1, true)));
+        assert_eq!(
+            self::clip(1, AnimationMode::PingPong).next_frame(0, true),
+            Some((0, true))
+        );
+    }
+
+    #[test]
+    fn durations_reuse_the_last_value() {
+        let clip = clip(3, AnimationMode::Once);
+        assert_eq!(clip.frame_duration(2), Duration::from_millis(50));
+        assert_eq!(clip.duration(), Duration::from_millis(200));
+    }
+
+    #[test]
+    fn still_clips_have_a_death_pose() {
+        let clips = still_clips("heroi");
+        assert_eq!(
+            clips[&AnimationClipName::Death].frames,
+            vec!["heroi".to_string()]
+        );
+        assert!(clips[&AnimationClipName::Death].duration() > Duration::from_millis(0));
+    }
+}
@@ -0,0 +1,84 @@
+use crate::{
+    component::{Background, Render},
+    resources::RenderLayer,
+    sprite::intern_sprite,
+};
+use serde::{Deserialize, Deserializer, Serialize};
+use specs::{Builder, World};
+
+/// One layer of a parallax background, drawn in screen space behind the world.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct BackgroundLayerConfig {
+    pub sprite: String,
+    /// How much the layer follows the camera: 0.0 stays still, 1.0 moves with the world.
+    pub scroll_factor: f32,
+    /// Tiles the sprite horizontally to fill the screen.
+    pub repeat_x: bool,
+    /// Horizontal speed in pixels per second, independent of the camera.
+    pub auto_scroll: f32,
+    pub offset_y: f32,
+}
+
+impl Default for BackgroundLayerConfig {
+    fn default() -> BackgroundLayerConfig {
+        BackgroundLayerConfig {
+            sprite: String::new(),
+            scroll_factor: 0.0,
+            repeat_x: false,
+            auto_scroll: 0.0,
+            offset_y: 0.0,
+        }
+    }
+}
+
+impl BackgroundLayerConfig {
+    pub fn fixed(sprite: &str) -> BackgroundLayerConfig {
+        BackgroundLayerConfig {
+            sprite: sprite.to_string(),
+            ..BackgroundLayerConfig::default()
+        }
+    }
+}
+
+/// Reads either a list of layers or a single sprite name, kept for older scene files.
+pub fn deserialize_layers<'de, D>(deserializer: D) -> Result<Vec<BackgroundLayerConfig>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Layers {
+        Sprite(String),
+        Layers(Vec<BackgroundLayerConfig>),
+    }
+
+    Ok(match Layers::deserialize(deserializer)? {
+        Layers::Sprite(sprite) => vec![BackgroundLayerConfig::fixed(&sprite)],
+        Layers::Layers(layers) => layers,
+    })
+}
+
+/// Spawns the layers back to front.
+pub fn create_background(world: &mut World, layers: &[BackgroundLayerConfig]) {
+    for (depth, layer) in layers.iter().enumerate() {
+        let sprite = intern_sprite(world, &layer.sprite);
+        world
+            .create_entity()
+            .with(Background {
+                depth,
+                scroll_factor: layer.scroll_factor,
+                repeat_x: layer.repeat_x,
+                auto_scroll: layer.auto_scroll,
+                offset_y: layer.offset_y,
+                scroll: 0.0,
+                width: 0.0,
+            })
+            .with(Render {
+                sprite,
+                layer: RenderLayer::Background,
+                z: 0.0,
+            })
+            .build();
+    }
+}
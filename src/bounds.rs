@@ -0,0 +1,193 @@
+use quicksilver::geom::{Rectangle, Vector};
+use rand::{thread_rng, Rng};
+use serde::{Deserialize, Serialize};
+
+/// Playable area of the scene, in world coordinates.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone)]
+#[serde(default)]
+pub struct WorldBounds {
+    pub position: Vector,
+    pub size: Vector,
+    /// How far past the bounds an offscreen entity may go before it is despawned or wrapped.
+    /// Keep it above `spawn_margin`, or entering enemies are despawned right away.
+    pub despawn_margin: f32,
+    /// Distance from the bounds where enemies enter the scene.
+    pub spawn_margin: f32,
+    /// Distance from the left and right edges clamped entities are kept at.
+    pub clamp_margin: f32,
+    /// Lifetime given to `AfterLifetime` entities that leave the bounds without one.
+    pub offscreen_lifetime_ms: u64,
+}
+
+impl Default for WorldBounds {
+    fn default() -> WorldBounds {
+        WorldBounds {
+            position: Vector::ZERO,
+            size: Vector::new(800.0, 600.0),
+            despawn_margin: 100.0,
+            spawn_margin: 50.0,
+            clamp_margin: 15.0,
+            offscreen_lifetime_ms: 2000,
+        }
+    }
+}
+
+impl WorldBounds {
+    pub fn area(&self) -> Rectangle {
+        Rectangle::new(self.position, self.size)
+    }
+
+    pub fn left(&self) -> f32 {
+        self.position.x
+    }
+
+    pub fn right(&self) -> f32 {
+        self.position.x + self.size.x
+    }
+
+    pub fn top(&self) -> f32 {
+        self.position.y
+    }
+
+    pub fn bottom(&self) -> f32 {
+        self.position.y + self.size.y
+    }
+
+    /// Warns about margins that would despawn entities as soon as they spawn.
+    pub fn check(&self) {
+        if self.despawn_margin < self.spawn_margin {
+            log::warn!(
+                "World bounds despawn margin {} is smaller than the spawn margin {}",
+                self.despawn_margin,
+                self.spawn_margin
+            );
+        }
+    }
+
+    pub fn is_outside(&self, position: Vector) -> bool {
+        let margin = self.despawn_margin;
+        position.x < self.left() - margin
+            || position.x > self.right() + margin
+            || position.y < self.top() - margin
+            || position.y > self.bottom() + margin
+    }
+
+    /// Where an entity entering `area`, usually the camera view, from the left or right starts.
+    pub fn side_spawn_x(&self, area: &Rectangle, left: bool) -> f32 {
+        if left {
+            area.pos.x - self.spawn_margin
+        } else {
+            area.pos.x + area.size.x + self.spawn_margin
+        }
+    }
+
+    /// Random point above `area`, usually the camera view, where falling pickups appear.
+    pub fn drop_position(&self, area: &Rectangle) -> Vector {
+        let (min, max) = (
+            area.pos.x + self.spawn_margin,
+            area.pos.x + area.size.x - self.spawn_margin,
+        );
+        let x = if min < max {
+            thread_rng().gen_range(min, max)
+        } else {
+            area.pos.x + area.size.x / 2.0
+        };
+        Vector::new(x, area.pos.y - self.spawn_margin)
+    }
+
+    pub fn clamp_x(&self, x: f32) -> f32 {
+        x.max(self.left() + self.clamp_margin)
+            .min(self.right() - self.clamp_margin)
+    }
+
+    /// Moves a position that left the bounds to the opposite side.
+    pub fn wrap(&self, position: Vector) -> Vector {
+        let margin = self.despawn_margin;
+        let wrap_axis = |value: f32, min: f32, max: f32| {
+            let (min, max) = (min - margin, max + margin);
+            if value < min {
+                value + (max - min)
+            } else if value > max {
+                value - (max - min)
+            } else {
+                value
+            }
+        };
+        Vector::new(
+            wrap_axis(position.x, self.left(), self.right()),
+            wrap_axis(position.y, self.top(), self.bottom()),
+        )
+    }
+}
+
+/// What happens to an entity when it leaves the world bounds.
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Copy, Clone)]
+pub enum DespawnPolicy {
+    Offscreen,
+    /// Removed once its `Lifetime` runs out, getting one when it leaves the bounds without it.
+    AfterLifetime,
+    Never,
+    WrapAround,
+    /// Kept horizontally inside the bounds.
+    Clamp,
+}
+
+impl Default for DespawnPolicy {
+    fn default() -> DespawnPolicy {
+        DespawnPolicy::Offscreen
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wrap_moves_to_the_opposite_side() {
+        let bounds = WorldBounds::default();
+        assert_eq!(
+            bounds.wrap(Vector::new(-101.0, 300.0)),
+            Vector::new(899.0, 300.0)
+        );
+        assert_eq!(
+            bounds.wrap(Vector::new(400.0, 701.0)),
+            Vector::new(400.0, -99.0)
+        );
+        assert_eq!(
+            bounds.wrap(Vector::new(400.0, 300.0)),
+            Vector::new(400.0, 300.0)
+        );
+    }
+
+    #[test]
+    fn spawns_are_not_outside() {
+        let bounds = WorldBounds::default();
+        let area = bounds.area();
+        for _ in 0..10 {
+            let drop = bounds.drop_position(&area);
+            assert!(drop.x >= 50.0 && drop.x <= 750.0);
+            assert!(!bounds.is_outside(drop));
+        }
+        assert!(!bounds.is_outside(Vector::new(bounds.side_spawn_x(&area, true), 300.0)));
+        assert!(!bounds.is_outside(Vector::new(bounds.side_spawn_x(&area, false), 300.0)));
+        assert!(bounds.is_outside(Vector::new(901.0, 300.0)));
+    }
+
+    #[test]
+    fn despawn_uses_the_configured_margin() {
+        let bounds = WorldBounds {
+            despawn_margin: 20.0,
+            ..WorldBounds::default()
+        };
+        assert!(!bounds.is_outside(Vector::new(819.0, 300.0)));
+        assert!(bounds.is_outside(Vector::new(821.0, 300.0)));
+    }
+
+    #[test]
+    fn clamp_keeps_the_margin() {
+        let bounds = WorldBounds::default();
+        assert_eq!(bounds.clamp_x(-20.0), 15.0);
+        assert_eq!(bounds.clamp_x(900.0), 785.0);
+        assert_eq!(bounds.clamp_x(400.0), 400.0);
+    }
+}
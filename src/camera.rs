@@ -0,0 +1,423 @@
+use std::{collections::HashMap, time::Duration};
+
+use quicksilver::{
+    geom::{Rectangle, Vector},
+    graphics::{Color, View},
+};
+use serde::{Deserialize, Serialize};
+use specs::Entity;
+
+use crate::bounds::WorldBounds;
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone)]
+#[serde(default)]
+pub struct CameraConfig {
+    /// Size of the window area the camera draws to.
+    pub viewport: Vector,
+    pub zoom: f32,
+    /// Area around the view center the target can move in without moving the camera.
+    pub dead_zone: Vector,
+    /// Fraction of the distance to the target covered each tick, 1.0 snaps to it.
+    pub smoothing: f32,
+}
+
+impl Default for CameraConfig {
+    fn default() -> CameraConfig {
+        CameraConfig {
+            viewport: Vector::new(800.0, 600.0),
+            zoom: 1.0,
+            dead_zone: Vector::new(160.0, 120.0),
+            smoothing: 0.15,
+        }
+    }
+}
+
+/// World-space view drawn to the window, following `target` inside the world bounds.
+pub struct Camera {
+    pub position: Vector,
+    pub zoom: f32,
+    pub viewport: Vector,
+    pub dead_zone: Vector,
+    pub smoothing: f32,
+    pub target: Option<Entity>,
+}
+
+impl Default for Camera {
+    fn default() -> Camera {
+        Camera::new(CameraConfig::default())
+    }
+}
+
+impl Camera {
+    pub fn new(config: CameraConfig) -> Camera {
+        Camera {
+            position: config.viewport / 2.0,
+            zoom: config.zoom,
+            viewport: config.viewport,
+            dead_zone: config.dead_zone,
+            smoothing: config.smoothing,
+            target: None,
+        }
+    }
+
+    pub fn follow(&mut self, target: Entity) {
+        self.target = Some(target);
+    }
+
+    /// Size of the world area in view.
+    pub fn view_size(&self) -> Vector {
+        self.viewport / self.zoom
+    }
+
+    pub fn view_area(&self) -> Rectangle {
+        Rectangle::new(self.position - self.view_size() / 2.0, self.view_size())
+    }
+
+    pub fn world_view(&self) -> View {
+        View::new(self.view_area())
+    }
+
+    /// World view moved by the screen shake.
+    pub fn shaken_view(&self, shake: Vector) -> View {
+        let area = self.view_area();
+        View::new(Rectangle::new(area.pos + shake, area.size))
+    }
+
+    pub fn screen_view(&self) -> View {
+        View::new(Rectangle::new(Vector::ZERO, self.viewport))
+    }
+
+    pub fn to_screen(&self, position: Vector) -> Vector {
+        (position - self.view_area().pos) * self.zoom
+    }
+
+    pub fn to_world(&self, position: Vector) -> Vector {
+        position / self.zoom + self.view_area().pos
+    }
+
+    /// Moves towards `target` once it leaves the dead zone, then keeps the view inside `bounds`.
+    pub fn update(&mut self, target: Vector, bounds: &WorldBounds) {
+        let half_dead_zone = self.dead_zone / 2.0;
+        let follow_axis = |position: f32, target: f32, half_dead_zone: f32| {
+            if target > position + half_dead_zone {
+                target - half_dead_zone
+            } else if target < position - half_dead_zone {
+                target + half_dead_zone
+            } else {
+                position
+            }
+        };
+        let goal = Vector::new(
+            follow_axis(self.position.x, target.x, half_dead_zone.x),
+            follow_axis(self.position.y, target.y, half_dead_zone.y),
+        );
+        self.position += (goal - self.position) * self.smoothing.max(0.0).min(1.0);
+        self.clamp(bounds);
+    }
+
+    /// Centers the view on `target` at once, e.g. when a run or a map starts.
+    pub fn snap_to(&mut self, target: Vector, bounds: &WorldBounds) {
+        self.position = target;
+        self.clamp(bounds);
+    }
+
+    pub fn clamp(&mut self, bounds: &WorldBounds) {
+        let half_view = self.view_size() / 2.0;
+        let clamp_axis = |position: f32, min: f32, max: f32, half_view: f32| {
+            if max - min <= half_view * 2.0 {
+                (min + max) / 2.0
+            } else {
+                position.max(min + half_view).min(max - half_view)
+            }
+        };
+        self.position = Vector::new(
+            clamp_axis(self.position.x, bounds.left(), bounds.right(), half_view.x),
+            clamp_axis(self.position.y, bounds.top(), bounds.bottom(), half_view.y),
+        );
+    }
+}
+
+/// Gameplay moments that can shake, freeze or flash the screen.
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Hash, Copy, Clone)]
+pub enum CameraEvent {
+    HeroHit,
+    HeroDefeated,
+    BossHit,
+    BossAngry,
+    BossDefeated,
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone)]
+#[serde(default)]
+pub struct FlashConfig {
+    pub color: (f32, f32, f32),
+    /// Opacity when the flash starts, fading to 0.0 over `duration_ms`.
+    pub alpha: f32,
+    pub duration_ms: u64,
+}
+
+impl Default for FlashConfig {
+    fn default() -> FlashConfig {
+        FlashConfig {
+            color: (1.0, 1.0, 1.0),
+            alpha: 0.6,
+            duration_ms: 150,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone)]
+#[serde(default)]
+pub struct CameraEffectConfig {
+    /// Added to the camera trauma, which is capped at 1.0.
+    pub trauma: f32,
+    /// Time the simulation stays frozen.
+    pub hit_stop_ms: u64,
+    pub flash: Option<FlashConfig>,
+}
+
+impl Default for CameraEffectConfig {
+    fn default() -> CameraEffectConfig {
+        CameraEffectConfig {
+            trauma: 0.0,
+            hit_stop_ms: 0,
+            flash: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct CameraEffectsConfig {
+    /// Accessibility toggle, turns shake, hit-stop and flashes off when false.
+    pub enabled: bool,
+    /// Largest shake offset, reached at full trauma.
+    pub max_shake: Vector,
+    /// Trauma lost per second.
+    pub trauma_decay: f32,
+    pub events: HashMap<CameraEvent, CameraEffectConfig>,
+}
+
+impl Default for CameraEffectsConfig {
+    fn default() -> CameraEffectsConfig {
+        CameraEffectsConfig {
+            enabled: true,
+            max_shake: Vector::new(12.0, 8.0),
+            trauma_decay: 1.5,
+            events: default_camera_events(),
+        }
+    }
+}
+
+pub fn default_camera_events() -> HashMap<CameraEvent, CameraEffectConfig> {
+    let mut events = HashMap::new();
+    events.insert(
+        CameraEvent::HeroHit,
+        CameraEffectConfig {
+            trauma: 0.5,
+            hit_stop_ms: 80,
+            flash: Some(FlashConfig {
+                color: (1.0, 0.2, 0.2),
+                alpha: 0.35,
+                duration_ms: 150,
+            }),
+        },
+    );
+    events.insert(
+        CameraEvent::HeroDefeated,
+        CameraEffectConfig {
+            trauma: 1.0,
+            hit_stop_ms: 200,
+            ..CameraEffectConfig::default()
+        },
+    );
+    events.insert(
+        CameraEvent::BossHit,
+        CameraEffectConfig {
+            trauma: 0.3,
+            hit_stop_ms: 50,
+            ..CameraEffectConfig::default()
+        },
+    );
+    events.insert(
+        CameraEvent::BossAngry,
+        CameraEffectConfig {
+            trauma: 0.8,
+            hit_stop_ms: 150,
+            flash: Some(FlashConfig {
+                duration_ms: 250,
+                ..FlashConfig::default()
+            }),
+        },
+    );
+    events.insert(
+        CameraEvent::BossDefeated,
+        CameraEffectConfig {
+            trauma: 1.0,
+            hit_stop_ms: 200,
+            flash: Some(FlashConfig {
+                alpha: 0.8,
+                duration_ms: 400,
+                ..FlashConfig::default()
+            }),
+        },
+    );
+    events
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct Flash {
+    pub config: FlashConfig,
+    pub remaining: Duration,
+}
+
+/// Trauma-based screen shake, hit-stop and screen flashes, triggered by `CameraEvent`s.
+pub struct CameraEffects {
+    pub config: CameraEffectsConfig,
+    pub trauma: f32,
+    /// Offset of the world view this frame.
+    pub shake: Vector,
+    pub hit_stop: Duration,
+    pub flash: Option<Flash>,
+}
+
+impl Default for CameraEffects {
+    fn default() -> CameraEffects {
+        CameraEffects::new(CameraEffectsConfig::default())
+    }
+}
+
+impl CameraEffects {
+    pub fn new(config: CameraEffectsConfig) -> CameraEffects {
+        CameraEffects {
+            config,
+            trauma: 0.0,
+            shake: Vector::ZERO,
+            hit_stop: Duration::from_millis(0),
+            flash: None,
+        }
+    }
+
+    pub fn trigger(&mut self, event: CameraEvent) {
+        if !self.config.enabled {
+            return;
+        }
+        let effect = match self.config.events.get(&event) {
+            Some(effect) => *effect,
+            None => return,
+        };
+        self.trauma = (self.trauma + effect.trauma).min(1.0);
+        self.hit_stop = self.hit_stop.max(Duration::from_millis(effect.hit_stop_ms));
+        if let Some(flash) = effect.flash {
+            self.flash = Some(Flash {
+                config: flash,
+                remaining: Duration::from_millis(flash.duration_ms),
+            });
+        }
+    }
+
+    /// Counts the hit-stop down, returning true while the simulation should stay frozen.
+    pub fn frozen(&mut self, time_step: Duration) -> bool {
+        if self.hit_stop == Duration::from_millis(0) {
+            return false;
+        }
+        self.hit_stop = self
+            .hit_stop
+            .checked_sub(time_step)
+            .unwrap_or_else(|| Duration::from_millis(0));
+        true
+    }
+
+    /// Decays the trauma and the flash. `noise` holds two values between -1.0 and 1.0.
+    pub fn update(&mut self, time_step: Duration, noise: (f32, f32)) {
+        let seconds = time_step.as_micros() as f32 / 1_000_000.0;
+        self.trauma = (self.trauma - self.config.trauma_decay * seconds).max(0.0);
+        // Squared so small hits barely move the screen while big ones shake it hard.
+        let shake = self.trauma * self.trauma;
+        self.shake = if self.config.enabled {
+            Vector::new(
+                self.config.max_shake.x * shake * noise.0,
+                self.config.max_shake.y * shake * noise.1,
+            )
+        } else {
+            Vector::ZERO
+        };
+
+        if let Some(flash) = &mut self.flash {
+            flash.remaining = flash
+                .remaining
+                .checked_sub(time_step)
+                .unwrap_or_else(|| Duration::from_millis(0));
+            if flash.remaining == Duration::from_millis(0) {
+                self.flash = None;
+            }
+        }
+    }
+
+    pub fn flash_color(&self) -> Option<Color> {
+        self.flash.map(|flash| {
+            let duration = flash.config.duration_ms.max(1) as f32;
+            let (r, g, b) = flash.config.color;
+            Color {
+                r,
+                g,
+                b,
+                a: flash.config.alpha * flash.remaining.as_millis() as f32 / duration,
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn camera(smoothing: f32) -> Camera {
+        Camera::new(CameraConfig {
+            smoothing,
+            ..CameraConfig::default()
+        })
+    }
+
+    fn bounds() -> WorldBounds {
+        WorldBounds {
+            size: Vector::new(2000.0, 600.0),
+            ..WorldBounds::default()
+        }
+    }
+
+    #[test]
+    fn target_inside_dead_zone_keeps_the_camera() {
+        let mut camera = camera(1.0);
+        camera.update(Vector::new(450.0, 320.0), &bounds());
+        assert_eq!(camera.position, Vector::new(400.0, 300.0));
+    }
+
+    #[test]
+    fn camera_follows_to_the_dead_zone_edge() {
+        let mut camera = camera(1.0);
+        camera.update(Vector::new(600.0, 300.0), &bounds());
+        assert_eq!(camera.position, Vector::new(520.0, 300.0));
+
+        let mut smooth = self::camera(0.5);
+        smooth.update(Vector::new(600.0, 300.0), &bounds());
+        assert_eq!(smooth.position, Vector::new(460.0, 300.0));
+    }
+
+    #[test]
+    fn view_stays_inside_the_bounds() {
+        let mut camera = camera(1.0);
+        camera.update(Vector::new(1990.0, 300.0), &bounds());
+        assert_eq!(camera.position, Vector::new(1600.0, 300.0));
+        camera.update(Vector::new(-500.0, 300.0), &bounds());
+        assert_eq!(camera.position, Vector::new(400.0, 300.0));
+    }
+
+    #[test]
+    fn snap_ignores_smoothing() {
+        let mut camera = camera(0.15);
+        camera.snap_to(Vector::new(1000.0, 300.0), &bounds());
+        assert_eq!(camera.position, Vector::new(1000.0, 300.0));
+        assert_eq!(camera.view_area().pos, Vector::new(600.0, 0.0));
+    }
+}
@@ -0,0 +1,383 @@
+use quicksilver::geom::{Rectangle, Vector};
+use serde::{de::Error, Deserialize, Deserializer, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Hash, Copy, Clone)]
+pub enum HitboxName {
+    Body,
+    Head,
+    Feet,
+    Hurtbox,
+}
+
+/// Exact shape inside a hitbox; `size` stays the axis-aligned box around it.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub enum HitboxShape {
+    Rectangle,
+    /// Circle inscribed in the hitbox size.
+    Circle,
+    /// Convex polygon, points relative to the hitbox center.
+    Polygon(Vec<Vector>),
+}
+
+impl Default for HitboxShape {
+    fn default() -> HitboxShape {
+        HitboxShape::Rectangle
+    }
+}
+
+/// Named collision area, `offset` is from the entity position to the hitbox center.
+#[derive(Serialize, Debug, Clone)]
+pub struct Hitbox {
+    pub name: HitboxName,
+    pub offset: Vector,
+    pub size: Vector,
+    pub shape: HitboxShape,
+}
+
+/// Hitbox as written in the config. Polygons may leave out `size`, it's derived from the points.
+#[derive(Deserialize)]
+struct HitboxConfig {
+    name: HitboxName,
+    offset: Vector,
+    #[serde(default)]
+    size: Option<Vector>,
+    #[serde(default)]
+    shape: HitboxShape,
+}
+
+/// Rejects concave polygons, which the separating axis test can't handle, and sizes polygons to
+/// their points so the broad phase finds all of them.
+impl<'de> Deserialize<'de> for Hitbox {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Hitbox, D::Error> {
+        let config = HitboxConfig::deserialize(deserializer)?;
+        match config.shape {
+            HitboxShape::Polygon(points) => {
+                if is_convex(&points) {
+                    Ok(Hitbox::polygon(config.name, config.offset, points))
+                } else {
+                    Err(D::Error::custom(
+                        "hitbox polygons need at least three points and must be convex",
+                    ))
+                }
+            }
+            shape => match config.size {
+                Some(size) => Ok(Hitbox {
+                    name: config.name,
+                    offset: config.offset,
+                    size,
+                    shape,
+                }),
+                None => Err(D::Error::missing_field("size")),
+            },
+        }
+    }
+}
+
+/// Whether the polygon has at least three points, turns the same way at every corner and winds
+/// around only once, which rules out self-intersecting ones like a pentagram.
+pub fn is_convex(points: &[Vector]) -> bool {
+    let mut turn = 0.0;
+    let mut total_turning = 0.0;
+    for i in 0..points.len() {
+        let edge = points[(i + 1) % points.len()] - points[i];
+        let next = points[(i + 2) % points.len()] - points[(i + 1) % points.len()];
+        let cross = edge.x * next.y - edge.y * next.x;
+        if cross * turn < 0.0 {
+            return false;
+        }
+        if cross != 0.0 {
+            turn = cross;
+        }
+        total_turning += cross.atan2(edge.dot(next));
+    }
+    let full_turn = 2.0 * std::f32::consts::PI;
+    points.len() >= 3 && turn != 0.0 && (total_turning.abs() - full_turn).abs() < 1e-3
+}
+
+impl Hitbox {
+    pub fn new(name: HitboxName, offset: Vector, size: Vector) -> Hitbox {
+        Hitbox {
+            name,
+            offset,
+            size,
+            shape: HitboxShape::Rectangle,
+        }
+    }
+
+    pub fn circle(name: HitboxName, offset: Vector, radius: f32) -> Hitbox {
+        Hitbox {
+            name,
+            offset,
+            size: Vector::new(radius * 2.0, radius * 2.0),
+            shape: HitboxShape::Circle,
+        }
+    }
+
+    pub fn polygon(name: HitboxName, offset: Vector, points: Vec<Vector>) -> Hitbox {
+        let extent = points.iter().fold(Vector::ZERO, |extent, point| {
+            Vector::new(extent.x.max(point.x.abs()), extent.y.max(point.y.abs()))
+        });
+        Hitbox {
+            name,
+            offset,
+            size: extent * 2.0,
+            shape: HitboxShape::Polygon(points),
+        }
+    }
+
+    pub fn area(&self, position: Vector) -> Rectangle {
+        Rectangle::new(position + self.offset - self.size / 2.0, self.size)
+    }
+
+    pub fn shape_at(&self, position: Vector) -> HitboxArea {
+        let center = position + self.offset;
+        match self.shape {
+            HitboxShape::Rectangle => {
+                let half = self.size / 2.0;
+                HitboxArea::Polygon(vec![
+                    center + Vector::new(-half.x, -half.y),
+                    center + Vector::new(half.x, -half.y),
+                    center + Vector::new(half.x, half.y),
+                    center + Vector::new(-half.x, half.y),
+                ])
+            }
+            HitboxShape::Circle => HitboxArea::Circle {
+                center,
+                radius: self.size.x.min(self.size.y) / 2.0,
+            },
+            HitboxShape::Polygon(ref points) => {
+                HitboxArea::Polygon(points.iter().map(|point| center + *point).collect())
+            }
+        }
+    }
+}
+
+/// Hitbox shape placed in the world, tested with the separating axis theorem.
+#[derive(Debug, Clone)]
+pub enum HitboxArea {
+    Polygon(Vec<Vector>),
+    Circle { center: Vector, radius: f32 },
+}
+
+impl HitboxArea {
+    pub fn overlaps(&self, other: &HitboxArea) -> bool {
+        match (self, other) {
+            (
+                HitboxArea::Circle { center, radius },
+                HitboxArea::Circle {
+                    center: other_center,
+                    radius: other_radius,
+                },
+            ) => (*center - *other_center).len2() < (radius + other_radius).powi(2),
+            (HitboxArea::Polygon(points), HitboxArea::Polygon(other_points)) => {
+                !edge_normals(points)
+                    .chain(edge_normals(other_points))
+                    .any(|axis| self.separated_on(other, axis))
+            }
+            (HitboxArea::Polygon(points), HitboxArea::Circle { center, .. })
+            | (HitboxArea::Circle { center, .. }, HitboxArea::Polygon(points)) => {
+                let closest = points
+                    .iter()
+                    .cloned()
+                    .min_by(|a, b| {
+                        (*a - *center)
+                            .len2()
+                            .partial_cmp(&(*b - *center).len2())
+                            .unwrap()
+                    })
+                    .unwrap_or(*center);
+                !edge_normals(points)
+                    .chain(Some(closest - *center))
+                    .any(|axis| self.separated_on(other, axis))
+            }
+        }
+    }
+
+    fn separated_on(&self, other: &HitboxArea, axis: Vector) -> bool {
+        if axis.len2() == 0.0 {
+            return false;
+        }
+        let (min, max) = self.project(axis);
+        let (other_min, other_max) = other.project(axis);
+        max <= other_min || other_max <= min
+    }
+
+    fn project(&self, axis: Vector) -> (f32, f32) {
+        match self {
+            HitboxArea::Polygon(points) => points.iter().fold(
+                (std::f32::INFINITY, std::f32::NEG_INFINITY),
+                |(min, max), point| {
+                    let projection = point.dot(axis);
+                    (min.min(projection), max.max(projection))
+                },
+            ),
+            HitboxArea::Circle { center, radius } => {
+                let projection = center.dot(axis);
+                let extent = radius * axis.len();
+                (projection - extent, projection + extent)
+            }
+        }
+    }
+}
+
+fn edge_normals<'a>(points: &'a [Vector]) -> impl Iterator<Item = Vector> + 'a {
+    (0..points.len()).map(move |i| {
+        let edge = points[(i + 1) % points.len()] - points[i];
+        Vector::new(-edge.y, edge.x)
+    })
+}
+
+pub fn body(size: Vector) -> Vec<Hitbox> {
+    vec![Hitbox::new(HitboxName::Body, Vector::ZERO, size)]
+}
+
+pub fn circle(radius: f32) -> Vec<Hitbox> {
+    vec![Hitbox::circle(HitboxName::Body, Vector::ZERO, radius)]
+}
+
+/// Body with a strip at the bottom used to stomp enemies.
+pub fn body_feet(size: Vector, feet_height: f32) -> Vec<Hitbox> {
+    vec![
+        Hitbox::new(
+            HitboxName::Body,
+            Vector::new(0.0, -feet_height / 2.0),
+            Vector::new(size.x, size.y - feet_height),
+        ),
+        Hitbox::new(
+            HitboxName::Feet,
+            Vector::new(0.0, (size.y - feet_height) / 2.0),
+            Vector::new(size.x, feet_height),
+        ),
+    ]
+}
+
+/// Body with a strip at the top that can be stomped.
+pub fn head_body(size: Vector, head_height: f32) -> Vec<Hitbox> {
+    vec![
+        Hitbox::new(
+            HitboxName::Head,
+            Vector::new(0.0, (head_height - size.y) / 2.0),
+            Vector::new(size.x, head_height),
+        ),
+        Hitbox::new(
+            HitboxName::Body,
+            Vector::new(0.0, head_height / 2.0),
+            Vector::new(size.x, size.y - head_height),
+        ),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rectangle(position: Vector, size: Vector) -> HitboxArea {
+        Hitbox::new(HitboxName::Body, Vector::ZERO, size).shape_at(position)
+    }
+
+    #[test]
+    fn rectangles_overlap_only_when_intersecting() {
+        let a = rectangle(Vector::ZERO, Vector::new(10.0, 10.0));
+        assert!(a.overlaps(&rectangle(Vector::new(8.0, 8.0), Vector::new(10.0, 10.0))));
+        assert!(!a.overlaps(&rectangle(Vector::new(10.0, 0.0), Vector::new(10.0, 10.0))));
+        assert!(!a.overlaps(&rectangle(Vector::new(30.0, 0.0), Vector::new(10.0, 10.0))));
+    }
+
+    #[test]
+    fn circles_overlap_by_distance() {
+        let circle = |x: f32| HitboxArea::Circle {
+            center: Vector::new(x, 0.0),
+            radius: 5.0,
+        };
+        assert!(circle(0.0).overlaps(&circle(9.0)));
+        assert!(!circle(0.0).overlaps(&circle(10.0)));
+    }
+
+    #[test]
+    fn circle_misses_rectangle_corner() {
+        let square = rectangle(Vector::ZERO, Vector::new(10.0, 10.0));
+        let near_corner = HitboxArea::Circle {
+            center: Vector::new(8.0, 8.0),
+            radius: 5.0,
+        };
+        let past_corner = HitboxArea::Circle {
+            center: Vector::new(10.0, 10.0),
+            radius: 5.0,
+        };
+        assert!(square.overlaps(&near_corner));
+        assert!(near_corner.overlaps(&square));
+        assert!(!square.overlaps(&past_corner));
+    }
+
+    #[test]
+    fn triangle_separated_on_its_slanted_edge() {
+        let triangle = Hitbox::polygon(
+            HitboxName::Body,
+            Vector::ZERO,
+            vec![
+                Vector::new(-10.0, 10.0),
+                Vector::new(10.0, -10.0),
+                Vector::new(10.0, 10.0),
+            ],
+        )
+        .shape_at(Vector::ZERO);
+        assert!(!triangle.overlaps(&rectangle(Vector::new(-8.0, -8.0), Vector::new(4.0, 4.0))));
+        assert!(triangle.overlaps(&rectangle(Vector::new(8.0, 8.0), Vector::new(4.0, 4.0))));
+    }
+
+    #[test]
+    fn convexity() {
+        let square = [
+            Vector::new(0.0, 0.0),
+            Vector::new(1.0, 0.0),
+            Vector::new(1.0, 1.0),
+            Vector::new(0.0, 1.0),
+        ];
+        let arrow = [
+            Vector::new(0.0, 0.0),
+            Vector::new(2.0, 1.0),
+            Vector::new(0.0, 2.0),
+            Vector::new(1.0, 1.0),
+        ];
+        let pentagram = (0..5)
+            .map(|i| {
+                let angle = i as f32 * 4.0 * std::f32::consts::PI / 5.0;
+                Vector::new(angle.cos(), angle.sin())
+            })
+            .collect::<Vec<_>>();
+        assert!(is_convex(&square));
+        assert!(!is_convex(&arrow));
+        assert!(!is_convex(&square[..2]));
+        assert!(!is_convex(&pentagram));
+    }
+
+    #[test]
+    fn concave_polygons_are_rejected_on_load() {
+        let hitbox = |points: &str| {
+            serde_json::from_str::<Hitbox>(&format!(
+                r#"{{"name": "Body", "offset": {{"x": 0, "y": 0}}, "size": {{"x": 4, "y": 4}},
+                    "shape": {{"Polygon": {}}}}}"#,
+                points
+            ))
+        };
+        assert!(hitbox(r#"[{"x": 0, "y": 0}, {"x": 2, "y": 0}, {"x": 0, "y": 2}]"#).is_ok());
+        assert!(hitbox(
+            r#"[{"x": 0, "y": 0}, {"x": 2, "y": 1}, {"x": 0, "y": 2}, {"x": 1, "y": 1}]"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn polygon_size_is_derived_from_the_points() {
+        let hitbox = serde_json::from_str::<Hitbox>(
+            r#"{"name": "Body", "offset": {"x": 0, "y": 0}, "size": {"x": 4, "y": 4},
+                "shape": {"Polygon": [{"x": -6, "y": 0}, {"x": 0, "y": -2}, {"x": 6, "y": 0}]}}"#,
+        )
+        .unwrap();
+        assert_eq!(hitbox.size, Vector::new(12.0, 4.0));
+        assert!(serde_json::from_str::<Hitbox>(
+            r#"{"name": "Body", "offset": {"x": 0, "y": 0}, "shape": "Circle"}"#
+        )
+        .is_err());
+    }
+}
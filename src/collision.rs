@@ -0,0 +1,132 @@
+use crate::{collider::HitboxName, particles::ParticleEffectName};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Hash, Copy, Clone)]
+pub enum CollisionLayerName {
+    Hero,
+    Enemy,
+    Pickup,
+    Projectile,
+    Wall,
+}
+
+impl CollisionLayerName {
+    pub fn bit(self) -> u32 {
+        1 << self as u32
+    }
+
+    pub fn mask(layers: &[CollisionLayerName]) -> u32 {
+        layers.iter().fold(0, |mask, layer| mask | layer.bit())
+    }
+}
+
+/// Layers an entity belongs to and the layers it wants to collide with.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CollisionLayerConfig {
+    pub layers: Vec<CollisionLayerName>,
+    pub mask: Vec<CollisionLayerName>,
+}
+
+impl CollisionLayerConfig {
+    pub fn new(layers: &[CollisionLayerName], mask: &[CollisionLayerName]) -> Self {
+        CollisionLayerConfig {
+            layers: layers.to_vec(),
+            mask: mask.to_vec(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Copy, Clone)]
+pub enum CollisionTarget {
+    First,
+    Second,
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Copy, Clone)]
+pub enum CollisionAction {
+    /// Target loses a life, bosses lose a boss life and anything else is destroyed.
+    Damage(CollisionTarget),
+    /// First entity stomps the second one, scoring it.
+    Stomp,
+    /// First entity collects the second one (healing or power-up).
+    Pickup,
+    /// Target reverses its velocity.
+    Bounce(CollisionTarget),
+    Destroy(CollisionTarget),
+    /// Target is pushed out of the other entity.
+    Block(CollisionTarget),
+    /// Plays a particle effect at the target position.
+    Emit(CollisionTarget, ParticleEffectName),
+}
+
+/// Response to an overlap between an entity on the `first` layer and one on the `second` layer.
+///
+/// A `None` hitbox uses the whole collider bounds. Actions run in order and the chain stops at
+/// the first one that has no effect, e.g. damage against a blinking hero. Only the first rule
+/// that matches a pair is applied each tick.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CollisionRule {
+    pub first: CollisionLayerName,
+    pub second: CollisionLayerName,
+    pub first_hitbox: Option<HitboxName>,
+    pub second_hitbox: Option<HitboxName>,
+    pub actions: Vec<CollisionAction>,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct CollisionRules {
+    pub rules: Vec<CollisionRule>,
+}
+
+pub fn default_collision_rules() -> Vec<CollisionRule> {
+    vec![
+        CollisionRule {
+            first: CollisionLayerName::Hero,
+            second: CollisionLayerName::Enemy,
+            first_hitbox: Some(HitboxName::Feet),
+            second_hitbox: Some(HitboxName::Head),
+            actions: vec![
+                CollisionAction::Emit(CollisionTarget::Second, ParticleEffectName::Stomp),
+                CollisionAction::Stomp,
+            ],
+        },
+        CollisionRule {
+            first: CollisionLayerName::Hero,
+            second: CollisionLayerName::Enemy,
+            first_hitbox: Some(HitboxName::Hurtbox),
+            second_hitbox: Some(HitboxName::Body),
+            actions: vec![
+                CollisionAction::Damage(CollisionTarget::First),
+                CollisionAction::Emit(CollisionTarget::First, ParticleEffectName::Hit),
+            ],
+        },
+        CollisionRule {
+            first: CollisionLayerName::Hero,
+            second: CollisionLayerName::Pickup,
+            first_hitbox: None,
+            second_hitbox: None,
+            actions: vec![
+                CollisionAction::Emit(CollisionTarget::Second, ParticleEffectName::Pickup),
+                CollisionAction::Pickup,
+            ],
+        },
+        CollisionRule {
+            first: CollisionLayerName::Hero,
+            second: CollisionLayerName::Projectile,
+            first_hitbox: Some(HitboxName::Hurtbox),
+            second_hitbox: None,
+            actions: vec![
+                CollisionAction::Damage(CollisionTarget::First),
+                CollisionAction::Emit(CollisionTarget::First, ParticleEffectName::Hit),
+                CollisionAction::Destroy(CollisionTarget::Second),
+            ],
+        },
+        CollisionRule {
+            first: CollisionLayerName::Projectile,
+            second: CollisionLayerName::Wall,
+            first_hitbox: None,
+            second_hitbox: None,
+            actions: vec![CollisionAction::Destroy(CollisionTarget::First)],
+        },
+    ]
+}
@@ -1,11 +1,23 @@
-use std::time::Duration;
+use std::{collections::HashMap, time::Duration};
 
-use crate::resources::LabelVariable;
+use crate::{
+    animation::{AnimationClip, AnimationClipName, AnimationClips},
+    bounds::DespawnPolicy,
+    collider::{Hitbox, HitboxArea, HitboxName},
+    collision::{CollisionLayerConfig, CollisionLayerName},
+    enemy::EnemyType,
+    particles::{ParticleEmitterConfig, ParticleOverLife},
+    power_up::PowerUpKind,
+    resources::{GameStateFlag, LabelVariable, RenderLayer},
+    sprite::{SpriteHandle, SpriteTable},
+    tween::TweenConfig,
+};
 
 use quicksilver::{
-    geom::{Rectangle, Vector},
-    graphics::FontStyle,
+    geom::{Rectangle, Transform, Vector},
+    graphics::{Color, FontStyle},
 };
+use serde::{Deserialize, Serialize};
 
 use specs::{world::Index, Component, NullStorage, VecStorage};
 
@@ -21,27 +33,309 @@ pub struct Velocity {
     pub velocity: Vector,
 }
 
-#[derive(Component, Debug, Clone)]
+#[derive(Component, Debug, Copy, Clone)]
 #[storage(VecStorage)]
 pub struct Render {
-    pub sprite: String,
-    pub bounding_box: Option<Rectangle>,
+    pub sprite: SpriteHandle,
+    pub layer: RenderLayer,
+    /// Order inside the layer, higher is drawn later.
+    pub z: f32,
+}
+
+/// Direction the unflipped image faces.
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Copy, Clone)]
+pub enum Facing {
+    Left,
+    Right,
+}
+
+/// How the sprite is drawn around its center. `rotation` is in degrees.
+#[derive(Component, Serialize, Deserialize, Debug, Copy, Clone)]
+#[storage(VecStorage)]
+#[serde(default)]
+pub struct SpriteTransform {
+    pub flip_x: bool,
+    pub flip_y: bool,
+    pub rotation: f32,
+    pub scale: Vector,
+    pub tint: (f32, f32, f32),
+    pub alpha: f32,
+    /// Flips the sprite horizontally to face where the entity is moving.
+    pub facing: Option<Facing>,
+    /// Draws the sprite away from the entity position.
+    pub offset: Vector,
+}
+
+impl Default for SpriteTransform {
+    fn default() -> SpriteTransform {
+        SpriteTransform {
+            flip_x: false,
+            flip_y: false,
+            rotation: 0.0,
+            scale: Vector::ONE,
+            tint: (1.0, 1.0, 1.0),
+            alpha: 1.0,
+            facing: None,
+            offset: Vector::ZERO,
+        }
+    }
+}
+
+impl SpriteTransform {
+    pub fn facing(facing: Facing) -> SpriteTransform {
+        SpriteTransform {
+            facing: Some(facing),
+            ..SpriteTransform::default()
+        }
+    }
+
+    pub fn transform(&self) -> Transform {
+        let flip = |flipped: bool| if flipped { -1.0 } else { 1.0 };
+        Transform::rotate(self.rotation)
+            * Transform::scale(Vector::new(
+                self.scale.x * flip(self.flip_x),
+                self.scale.y * flip(self.flip_y),
+            ))
+    }
+
+    pub fn color(&self) -> Color {
+        Color {
+            r: self.tint.0,
+            g: self.tint.1,
+            b: self.tint.2,
+            a: self.alpha,
+        }
+    }
+}
+
+/// Named clips switched by state, writing the current frame into `Render::sprite`.
+#[derive(Component, Debug, Clone)]
+#[storage(VecStorage)]
+pub struct Animation {
+    pub clips: AnimationClips,
+    /// Clip frames interned by `resolve`.
+    pub frames: HashMap<AnimationClipName, Vec<SpriteHandle>>,
+    pub current: AnimationClipName,
+    pub frame: usize,
+    pub forward: bool,
+    pub elapsed: Duration,
+    pub finished: bool,
+}
+
+impl Animation {
+    pub fn new(clips: AnimationClips) -> Animation {
+        Animation {
+            clips,
+            frames: HashMap::new(),
+            current: AnimationClipName::Idle,
+            frame: 0,
+            forward: true,
+            elapsed: Duration::from_millis(0),
+            finished: false,
+        }
+    }
+
+    /// Restarts with another clip, if the entity has it.
+    pub fn play(&mut self, name: AnimationClipName) {
+        if self.current != name && self.clips.contains_key(&name) {
+            self.current = name;
+            self.frame = 0;
+            self.forward = true;
+            self.elapsed = Duration::from_millis(0);
+            self.finished = false;
+        }
+    }
+
+    pub fn clip(&self) -> Option<&AnimationClip> {
+        self.clips.get(&self.current)
+    }
+
+    /// Interns the frames of every clip, the first time it's called.
+    pub fn resolve(&mut self, sprites: &mut SpriteTable) {
+        if !self.frames.is_empty() {
+            return;
+        }
+        for (name, clip) in &self.clips {
+            let frames = clip.frames.iter().map(|frame| sprites.intern(frame));
+            self.frames.insert(*name, frames.collect());
+        }
+    }
+
+    pub fn sprite(&self) -> Option<SpriteHandle> {
+        self.frames
+            .get(&self.current)
+            .and_then(|frames| frames.get(self.frame))
+            .cloned()
+    }
+
+    pub fn advance(&mut self, time: Duration) {
+        if self.finished {
+            return;
+        }
+        self.elapsed += time;
+        loop {
+            let clip = match self.clips.get(&self.current) {
+                Some(clip) if !clip.frames.is_empty() => clip,
+                _ => return,
+            };
+            let duration = clip.frame_duration(self.frame);
+            if duration == Duration::from_millis(0) || self.elapsed < duration {
+                return;
+            }
+            self.elapsed -= duration;
+            match clip.next_frame(self.frame, self.forward) {
+                Some((frame, forward)) => {
+                    self.frame = frame;
+                    self.forward = forward;
+                }
+                None => {
+                    self.finished = true;
+                    return;
+                }
+            }
+        }
+    }
+}
+
+#[derive(Component, Debug, Clone)]
+#[storage(VecStorage)]
+pub struct Collider {
+    pub hitboxes: Vec<Hitbox>,
+}
+
+impl Collider {
+    pub fn hitbox(&self, name: HitboxName) -> Option<&Hitbox> {
+        self.hitboxes.iter().find(|hitbox| hitbox.name == name)
+    }
+
+    pub fn area(&self, name: HitboxName, position: Vector) -> Option<Rectangle> {
+        self.hitbox(name).map(|hitbox| hitbox.area(position))
+    }
+
+    /// Exact shapes of a named hitbox, `None` meaning every hitbox. Hurtboxes fall back to the
+    /// body and then to every hitbox, like `hurt_area`.
+    pub fn shapes(&self, name: Option<HitboxName>, position: Vector) -> Vec<HitboxArea> {
+        let hitbox = match name {
+            None => None,
+            Some(HitboxName::Hurtbox) => self
+                .hitbox(HitboxName::Hurtbox)
+                .or_else(|| self.hitbox(HitboxName::Body)),
+            Some(name) => match self.hitbox(name) {
+                Some(hitbox) => Some(hitbox),
+                None => return Vec::new(),
+            },
+        };
+        match hitbox {
+            Some(hitbox) => vec![hitbox.shape_at(position)],
+            None => self
+                .hitboxes
+                .iter()
+                .map(|hitbox| hitbox.shape_at(position))
+                .collect(),
+        }
+    }
+
+    /// Smallest hitbox dimension, used to size the steps of continuous collision.
+    pub fn min_extent(&self) -> f32 {
+        self.hitboxes
+            .iter()
+            .map(|hitbox| hitbox.size.x.min(hitbox.size.y))
+            .fold(std::f32::INFINITY, f32::min)
+    }
+
+    /// Area where the entity takes damage: the hurtbox, else the body, else the whole bounds.
+    pub fn hurt_area(&self, position: Vector) -> Rectangle {
+        self.area(HitboxName::Hurtbox, position)
+            .or_else(|| self.area(HitboxName::Body, position))
+            .unwrap_or_else(|| self.bounds(position))
+    }
+
+    pub fn bounds(&self, position: Vector) -> Rectangle {
+        let mut areas = self.hitboxes.iter().map(|hitbox| hitbox.area(position));
+        match areas.next() {
+            Some(first) => areas.fold(first, |bounds, area| {
+                let top_left =
+                    Vector::new(bounds.pos.x.min(area.pos.x), bounds.pos.y.min(area.pos.y));
+                let bottom_right = Vector::new(
+                    (bounds.pos.x + bounds.size.x).max(area.pos.x + area.size.x),
+                    (bounds.pos.y + bounds.size.y).max(area.pos.y + area.size.y),
+                );
+                Rectangle::new(top_left, bottom_right - top_left)
+            }),
+            None => Rectangle::new(position, Vector::ZERO),
+        }
+    }
+
+    /// Bounds covering the whole movement from `previous` to `position`.
+    pub fn swept_bounds(&self, previous: Vector, position: Vector) -> Rectangle {
+        let start = self.bounds(previous);
+        let end = self.bounds(position);
+        let top_left = Vector::new(start.pos.x.min(end.pos.x), start.pos.y.min(end.pos.y));
+        let bottom_right = Vector::new(
+            (start.pos.x + start.size.x).max(end.pos.x + end.size.x),
+            (start.pos.y + start.size.y).max(end.pos.y + end.size.y),
+        );
+        Rectangle::new(top_left, bottom_right - top_left)
+    }
+}
+
+/// Marks fast entities whose collisions are tested along their whole movement each tick.
+#[derive(Component, Debug, Default, Copy, Clone)]
+#[storage(VecStorage)]
+pub struct ContinuousCollision {
+    pub previous: Option<Vector>,
+}
+
+#[derive(Component, Debug, Copy, Clone)]
+#[storage(VecStorage)]
+pub struct CollisionLayer {
+    pub membership: u32,
+    pub mask: u32,
+}
+
+impl CollisionLayer {
+    pub fn new(config: &CollisionLayerConfig) -> CollisionLayer {
+        CollisionLayer {
+            membership: CollisionLayerName::mask(&config.layers),
+            mask: CollisionLayerName::mask(&config.mask),
+        }
+    }
+
+    pub fn is_in(&self, layer: CollisionLayerName) -> bool {
+        self.membership & layer.bit() != 0
+    }
+
+    pub fn collides_with(&self, other: &CollisionLayer) -> bool {
+        self.mask & other.membership != 0
+    }
 }
 
 #[derive(Component, Debug, Clone)]
 #[storage(VecStorage)]
 pub struct Shooter {
     pub projectile_sprite: String,
+    pub projectile_collider: Vec<Hitbox>,
+    pub projectile_layer: CollisionLayerConfig,
+    pub projectile_render_layer: RenderLayer,
+    pub fast_projectiles: bool,
+    pub projectile_trail: Option<ParticleEmitterConfig>,
     pub maximum_fireballs: i32,
     pub fireball_amount: i32,
     pub coefficient: (f32, f32),
+    /// Holds fire while false; set once the entity's tween finishes, e.g. the boss entrance.
+    pub active: bool,
 }
 
 #[derive(Component, Debug, Copy, Clone)]
 #[storage(VecStorage)]
 pub struct Label {
     pub bind_variable: LabelVariable,
+    /// String key of the text, `{value}` being replaced by the variable.
+    pub template: &'static str,
     pub font_style: FontStyle,
+    pub layer: RenderLayer,
+    /// Order inside the layer, higher is drawn later.
+    pub z: f32,
 }
 
 #[derive(Component, Debug, Copy, Clone)]
@@ -53,6 +347,9 @@ pub struct Hero {
     pub render: bool,
     pub reset_position: bool,
     pub blink_timer: Duration,
+    pub air_jumps: i32,
+    pub jump_pressed: bool,
+    pub invincible: bool,
 }
 
 #[derive(Component, Debug, Copy, Clone)]
@@ -62,10 +359,10 @@ pub struct Boss {
     pub normal_lives: i32,
 }
 
-#[derive(Component, Debug, Clone)]
+#[derive(Component, Debug, Copy, Clone)]
 #[storage(VecStorage)]
 pub struct ChangeSprite {
-    pub new_sprite: String,
+    pub new_sprite: SpriteHandle,
     pub do_change: bool,
 }
 
@@ -73,6 +370,8 @@ pub struct ChangeSprite {
 #[storage(VecStorage)]
 pub struct Enemy {
     pub score: i32,
+    /// `None` for the boss and enemies made from custom configs.
+    pub kind: Option<EnemyType>,
 }
 
 #[derive(Component, Debug, Default, Copy, Clone)]
@@ -81,16 +380,194 @@ pub struct Healing {
     pub score: i32,
 }
 
+#[derive(Component, Debug, Copy, Clone)]
+#[storage(VecStorage)]
+pub struct RigidBody {
+    pub gravity_scale: f32,
+    pub grounded: bool,
+}
+
+/// Parallax layer drawn behind the world, see `background::BackgroundLayerConfig`.
 #[derive(Component, Debug, Default, Copy, Clone)]
-#[storage(NullStorage)]
-pub struct Background;
+#[storage(VecStorage)]
+pub struct Background {
+    pub depth: usize,
+    pub scroll_factor: f32,
+    pub repeat_x: bool,
+    pub auto_scroll: f32,
+    pub offset_y: f32,
+    /// Distance covered by `auto_scroll` so far, kept under `width` for repeated layers.
+    pub scroll: f32,
+    /// Width of the layer image, known once it has been drawn.
+    pub width: f32,
+}
 
 #[derive(Component, Debug, Default, Copy, Clone)]
 #[storage(NullStorage)]
-pub struct CalculateOutOfBounds;
+pub struct MapTile;
+
+#[derive(Component, Debug, Default, Copy, Clone)]
+#[storage(VecStorage)]
+pub struct OutOfBounds {
+    pub policy: DespawnPolicy,
+}
+
+/// Playing the death clip, the entity is deleted and `flag` raised once `remaining` runs out.
+#[derive(Component, Debug, Copy, Clone)]
+#[storage(VecStorage)]
+pub struct Dying {
+    pub remaining: Duration,
+    pub flag: Option<GameStateFlag>,
+}
+
+/// Playing the hurt clip for `remaining`.
+#[derive(Component, Debug, Copy, Clone)]
+#[storage(VecStorage)]
+pub struct Hurt {
+    pub remaining: Duration,
+}
+
+/// Deletes the entity once `remaining` runs out.
+#[derive(Component, Debug, Copy, Clone)]
+#[storage(VecStorage)]
+pub struct Lifetime {
+    pub remaining: Duration,
+}
+
+/// Spawns particles around the entity position.
+#[derive(Component, Debug, Clone)]
+#[storage(VecStorage)]
+pub struct ParticleEmitter {
+    pub config: ParticleEmitterConfig,
+    pub elapsed: Duration,
+    /// Fraction of a particle carried over to the next tick by continuous emitters.
+    pub pending: f32,
+    /// Deletes the entity once the emitter is done, for emitters spawned on their own.
+    pub despawn: bool,
+    /// `config.sprite`, interned on the first emission.
+    pub sprite: Option<SpriteHandle>,
+}
+
+/// Plays a `TweenConfig` on the entity position and sprite transform.
+#[derive(Component, Debug, Clone)]
+#[storage(VecStorage)]
+pub struct Tween {
+    pub config: TweenConfig,
+    pub step: usize,
+    pub elapsed: Duration,
+    pub forward: bool,
+    pub cycles: u32,
+    /// Progress of the current `Move` step already applied to the position.
+    pub applied: f32,
+    pub finished: bool,
+}
+
+#[derive(Component, Debug, Copy, Clone)]
+#[storage(VecStorage)]
+pub struct Particle {
+    pub age: Duration,
+    pub lifetime: Duration,
+    pub gravity: f32,
+    pub over_life: ParticleOverLife,
+}
+
+impl Particle {
+    /// Fraction of the lifetime already lived, from 0.0 to 1.0.
+    pub fn progress(&self) -> f32 {
+        let lifetime = self.lifetime.as_millis() as f32;
+        if lifetime > 0.0 {
+            (self.age.as_millis() as f32 / lifetime).min(1.0)
+        } else {
+            1.0
+        }
+    }
+}
 
 #[derive(Component, Debug, Default, Copy, Clone)]
 #[storage(VecStorage)]
 pub struct Fireball {
     pub owner_id: Option<Index>,
 }
+
+#[derive(Component, Debug, Copy, Clone)]
+#[storage(VecStorage)]
+pub struct PowerUp {
+    pub kind: PowerUpKind,
+    pub duration: Duration,
+    pub magnitude: f32,
+    pub score: i32,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct ActiveEffect {
+    pub kind: PowerUpKind,
+    pub magnitude: f32,
+    pub remaining: Duration,
+}
+
+#[derive(Component, Debug, Default, Clone)]
+#[storage(VecStorage)]
+pub struct ActiveEffects {
+    pub effects: Vec<ActiveEffect>,
+}
+
+impl ActiveEffects {
+    pub fn apply(&mut self, power_up: &PowerUp) {
+        self.remove(power_up.kind);
+        self.effects.push(ActiveEffect {
+            kind: power_up.kind,
+            magnitude: power_up.magnitude,
+            remaining: power_up.duration,
+        });
+    }
+
+    pub fn get(&self, kind: PowerUpKind) -> Option<&ActiveEffect> {
+        self.effects.iter().find(|effect| effect.kind == kind)
+    }
+
+    pub fn remove(&mut self, kind: PowerUpKind) -> bool {
+        let len = self.effects.len();
+        self.effects.retain(|effect| effect.kind != kind);
+        self.effects.len() != len
+    }
+
+    pub fn multiplier(&self, kind: PowerUpKind) -> f32 {
+        self.get(kind).map_or(1.0, |effect| effect.magnitude)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn power_up(kind: PowerUpKind, magnitude: f32, duration_ms: u64) -> PowerUp {
+        PowerUp {
+            kind,
+            duration: Duration::from_millis(duration_ms),
+            magnitude,
+            score: 0,
+        }
+    }
+
+    #[test]
+    fn active_effects_replace_the_same_kind() {
+        let mut effects = ActiveEffects::default();
+        effects.apply(&power_up(PowerUpKind::SpeedBoost, 1.5, 1000));
+        effects.apply(&power_up(PowerUpKind::SpeedBoost, 2.0, 3000));
+        assert_eq!(effects.effects.len(), 1);
+        let effect = effects.get(PowerUpKind::SpeedBoost).unwrap();
+        assert_eq!(effect.magnitude, 2.0);
+        assert_eq!(effect.remaining, Duration::from_millis(3000));
+    }
+
+    #[test]
+    fn multiplier_defaults_to_one() {
+        let mut effects = ActiveEffects::default();
+        assert_eq!(effects.multiplier(PowerUpKind::ScoreMultiplier), 1.0);
+        effects.apply(&power_up(PowerUpKind::ScoreMultiplier, 2.0, 1000));
+        assert_eq!(effects.multiplier(PowerUpKind::ScoreMultiplier), 2.0);
+        assert!(effects.remove(PowerUpKind::ScoreMultiplier));
+        assert!(!effects.remove(PowerUpKind::ScoreMultiplier));
+        assert_eq!(effects.multiplier(PowerUpKind::ScoreMultiplier), 1.0);
+    }
+}
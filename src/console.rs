@@ -0,0 +1,331 @@
+use std::{cell::RefCell, collections::BTreeMap, rc::Rc};
+
+use quicksilver::{
+    geom::{Rectangle, Shape, Vector},
+    graphics::{Background::Col, Background::Img, Color, Font, FontStyle},
+    input::{ButtonState, Key},
+    lifecycle::{Asset, Event, Window},
+    Result,
+};
+use serde::de::DeserializeOwned;
+use specs::{Join, World};
+
+use crate::{
+    camera::Camera,
+    component::{Hero, Position},
+    enemy::EnemyType,
+    entity_factory::PrefabsConfig,
+    healing::HealingConfig,
+    label::LabelCache,
+    resources::{PressedKeys, SceneCommand, SceneCommands, TimeScale},
+};
+
+pub type CommandResult = std::result::Result<String, String>;
+pub type CommandHandler = Box<dyn Fn(&mut World, &[&str]) -> CommandResult>;
+
+const OUTPUT_LINES: usize = 12;
+const LINE_HEIGHT: f32 = 18.0;
+
+struct ConsoleCommand {
+    help: String,
+    handler: CommandHandler,
+}
+
+/// Drop-down console, opened with the backquote key, running typed commands against the
+/// world. `help`, `history` and `clear` are handled by the console itself.
+pub struct Console {
+    pub open: bool,
+    input: String,
+    history: Vec<String>,
+    /// Entry of `history` shown in the input while browsing it with the arrow keys.
+    browsing: Option<usize>,
+    output: Vec<String>,
+    commands: BTreeMap<String, ConsoleCommand>,
+    cache: LabelCache,
+}
+
+impl Default for Console {
+    fn default() -> Console {
+        let mut console = Console {
+            open: false,
+            input: String::new(),
+            history: Vec::new(),
+            browsing: None,
+            output: Vec::new(),
+            commands: BTreeMap::new(),
+            cache: LabelCache::new(64),
+        };
+        register_default_commands(&mut console);
+        console
+    }
+}
+
+impl Console {
+    /// Adds a command, replacing any other with the same name.
+    pub fn register<F>(&mut self, name: &str, help: &str, handler: F)
+    where
+        F: Fn(&mut World, &[&str]) -> CommandResult + 'static,
+    {
+        self.commands.insert(
+            name.to_string(),
+            ConsoleCommand {
+                help: help.to_string(),
+                handler: Box::new(handler),
+            },
+        );
+    }
+
+    pub fn toggle(&mut self, world: &mut World) {
+        self.open = !self.open;
+        // Keys held when the console opens would never see their release.
+        world.write_resource::<PressedKeys>().pressed_keys.clear();
+    }
+
+    /// Returns whether the console took the event.
+    pub fn event(&mut self, event: &Event, world: &mut World) -> bool {
+        if let Event::Key(Key::Grave, ButtonState::Pressed) = event {
+            self.toggle(world);
+            return true;
+        }
+        if !self.open {
+            return false;
+        }
+        match event {
+            Event::Typed(c) if *c != '`' && !c.is_control() => self.input.push(*c),
+            Event::Key(Key::Back, ButtonState::Pressed) => {
+                self.input.pop();
+            }
+            Event::Key(Key::Return, ButtonState::Pressed) => {
+                let line = std::mem::replace(&mut self.input, String::new());
+                self.execute(world, &line);
+            }
+            Event::Key(Key::Escape, ButtonState::Pressed) => self.toggle(world),
+            Event::Key(Key::Up, ButtonState::Pressed) => {
+                let index = match self.browsing {
+                    Some(index) => index.saturating_sub(1),
+                    None => self.history.len().saturating_sub(1),
+                };
+                if let Some(line) = self.history.get(index) {
+                    self.input = line.clone();
+                    self.browsing = Some(index);
+                }
+            }
+            Event::Key(Key::Down, ButtonState::Pressed) => {
+                if let Some(index) = self.browsing {
+                    self.browsing = Some(index + 1).filter(|index| *index < self.history.len());
+                    self.input = self
+                        .browsing
+                        .map_or_else(String::new, |index| self.history[index].clone());
+                }
+            }
+            _ => {}
+        }
+        true
+    }
+
+    pub fn execute(&mut self, world: &mut World, line: &str) {
+        let line = line.trim();
+        if line.is_empty() {
+            return;
+        }
+        self.browsing = None;
+        self.history.push(line.to_string());
+        self.print(format!("> {}", line));
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let (name, args) = (words[0], &words[1..]);
+        let result = match name {
+            "help" => Ok(self
+                .commands
+                .iter()
+                .map(|(name, command)| format!("{}: {}", name, command.help))
+                .collect::<Vec<_>>()
+                .join("\n")),
+            "history" => Ok(self.history.join("\n")),
+            "clear" => {
+                self.output.clear();
+                return;
+            }
+            _ => match self.commands.get(name) {
+                Some(command) => (command.handler)(world, args),
+                None => Err(format!("Unknown command '{}', try 'help'", name)),
+            },
+        };
+        match result {
+            Ok(message) => self.print(message),
+            Err(message) => self.print(format!("Error: {}", message)),
+        }
+    }
+
+    fn print(&mut self, message: String) {
+        log::info!("{}", message);
+        self.output.extend(
+            message
+                .lines()
+                .filter(|line| !line.is_empty())
+                .map(String::from),
+        );
+        if self.output.len() > OUTPUT_LINES {
+            self.output.drain(..self.output.len() - OUTPUT_LINES);
+        }
+    }
+
+    pub fn draw(
+        &mut self,
+        window: &mut Window,
+        font: &Rc<RefCell<Asset<Font>>>,
+        screen_area: Rectangle,
+    ) -> Result<()> {
+        if !self.open {
+            return Ok(());
+        }
+        let height = (OUTPUT_LINES + 1) as f32 * LINE_HEIGHT + 8.0;
+        window.draw(
+            &Rectangle::new(Vector::ZERO, Vector::new(screen_area.width(), height)),
+            Col(Color::BLACK.with_alpha(0.75)),
+        );
+        let prompt = format!("> {}_", self.input);
+        let lines: Vec<&str> = self
+            .output
+            .iter()
+            .map(String::as_str)
+            .chain(Some(prompt.as_str()))
+            .collect();
+        let cache = &mut self.cache;
+        let style = FontStyle::new(16.0, Color::WHITE);
+        font.borrow_mut().execute(|font| {
+            for (line, text) in lines.iter().enumerate() {
+                let image = cache.get_or_render(text, &style, || font.render(text, &style))?;
+                let top_left = Vector::new(8.0, 4.0 + line as f32 * LINE_HEIGHT);
+                window.draw(&image.area().translate(top_left), Img(image));
+            }
+            Ok(())
+        })
+    }
+}
+
+fn parse<T: std::str::FromStr>(
+    args: &[&str],
+    index: usize,
+    name: &str,
+) -> std::result::Result<T, String> {
+    let arg = args
+        .get(index)
+        .ok_or_else(|| format!("Missing argument '{}'", name))?;
+    arg.parse()
+        .map_err(|_| format!("Invalid {} '{}'", name, arg))
+}
+
+/// Parses a serialized enum variant by name, e.g. `Walker` for `EnemyType::Walker`.
+fn parse_variant<T: DeserializeOwned>(name: &str) -> Option<T> {
+    serde_json::from_str(&format!("\"{}\"", name)).ok()
+}
+
+fn with_hero<F>(world: &mut World, f: F) -> CommandResult
+where
+    F: FnOnce(&mut Hero) -> String,
+{
+    let mut heroes = world.write_storage::<Hero>();
+    match (&mut heroes).join().next() {
+        Some(hero) => Ok(f(hero)),
+        None => Err("There is no hero".to_string()),
+    }
+}
+
+fn register_default_commands(console: &mut Console) {
+    console.register(
+        "spawn",
+        "spawn <EnemyType|Healing> [x y], at the view center by default",
+        |world, args| {
+            let name = args.get(0).ok_or("Missing what to spawn")?;
+            let position = if args.len() >= 3 {
+                Vector::new(parse::<f32>(args, 1, "x")?, parse::<f32>(args, 2, "y")?)
+            } else {
+                world.read_resource::<Camera>().position
+            };
+            if *name == "Healing" {
+                let healing = world.read_resource::<PrefabsConfig>().healing.clone();
+                crate::healing::create_healing_potion(
+                    world,
+                    HealingConfig {
+                        position,
+                        ..healing
+                    },
+                );
+            } else if let Some(enemy_type) = parse_variant::<EnemyType>(name) {
+                crate::enemy::create_enemy_at(world, enemy_type, position);
+            } else {
+                return Err(format!("Nothing called '{}' to spawn", name));
+            }
+            Ok(format!("Spawned {} at {:?}", name, position))
+        },
+    );
+    console.register("lives", "lives <n>, sets the hero lives", |world, args| {
+        let lives = parse(args, 0, "lives")?;
+        with_hero(world, |hero| {
+            hero.lives = lives;
+            format!("Lives set to {}", lives)
+        })
+    });
+    console.register("score", "score <n>, sets the hero score", |world, args| {
+        let score = parse(args, 0, "score")?;
+        with_hero(world, |hero| {
+            hero.score = score;
+            format!("Score set to {}", score)
+        })
+    });
+    console.register("god", "toggles hero invincibility", |world, _| {
+        with_hero(world, |hero| {
+            hero.invincible = !hero.invincible;
+            format!(
+                "Invincibility {}",
+                if hero.invincible { "on" } else { "off" }
+            )
+        })
+    });
+    console.register("boss", "skips to the boss fight", |world, _| {
+        world
+            .write_resource::<SceneCommands>()
+            .pending
+            .push(SceneCommand::SkipToBoss);
+        Ok("Skipping to the boss".to_string())
+    });
+    console.register(
+        "timescale",
+        "timescale <scale>, 1.0 is normal speed",
+        |world, args| {
+            let scale: f32 = parse(args, 0, "scale")?;
+            if scale < 0.0 {
+                return Err("The scale can't be negative".to_string());
+            }
+            world.write_resource::<TimeScale>().scale = scale;
+            Ok(format!("Time scale set to {}", scale))
+        },
+    );
+    console.register("reload", "reloads the scene config", |world, _| {
+        world
+            .write_resource::<SceneCommands>()
+            .pending
+            .push(SceneCommand::ReloadConfig);
+        Ok("Reloading the scene config".to_string())
+    });
+    console.register("entities", "lists the entities", |world, _| {
+        let entities = world.entities();
+        let positions = world.read_storage::<Position>();
+        let mut lines = Vec::new();
+        for entity in entities.join() {
+            let components = crate::debug::component_names(world, entity);
+            let position = positions.get(entity).map_or(String::new(), |position| {
+                format!(" at {:?}", position.position)
+            });
+            lines.push(format!(
+                "#{} gen {}{}: {}",
+                entity.id(),
+                entity.gen().id(),
+                position,
+                components.join(", ")
+            ));
+        }
+        lines.push(format!("{} entities", lines.len()));
+        Ok(lines.join("\n"))
+    });
+}
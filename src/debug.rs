@@ -0,0 +1,237 @@
+use std::{cell::RefCell, rc::Rc};
+
+use quicksilver::{
+    geom::{Circle, Line, Rectangle, Shape, Triangle, Vector},
+    graphics::{Background::Col, Background::Img, Color, Font, FontStyle, View},
+    lifecycle::{Asset, Window},
+    Result,
+};
+use specs::{Entity, Join, ReadStorage, World};
+
+use crate::{
+    camera::Camera,
+    collider::{HitboxArea, HitboxName},
+    component::*,
+    label::LabelCache,
+};
+
+/// Seconds of travel drawn for each velocity vector.
+const VELOCITY_SCALE: f32 = 0.1;
+const TEXT_SIZE: f32 = 12.0;
+const LINE_HEIGHT: f32 = 14.0;
+
+macro_rules! debug_components {
+    ($($component:ident),* $(,)*) => {
+        /// Names of the components `entity` has.
+        pub(crate) fn component_names(world: &World, entity: Entity) -> Vec<&'static str> {
+            let mut names = Vec::new();
+            $(
+                if world.read_storage::<$component>().contains(entity) {
+                    names.push(stringify!($component));
+                }
+            )*
+            names
+        }
+
+        /// Entities per component type, leaving out the ones without any.
+        fn component_counts(world: &World) -> Vec<(&'static str, usize)> {
+            let mut counts = Vec::new();
+            $(
+                let count = world.read_storage::<$component>().join().count();
+                if count > 0 {
+                    counts.push((stringify!($component), count));
+                }
+            )*
+            counts
+        }
+    };
+}
+
+debug_components!(
+    Position,
+    Velocity,
+    Render,
+    Collider,
+    CollisionLayer,
+    ContinuousCollision,
+    Shooter,
+    Label,
+    Hero,
+    Boss,
+    ChangeSprite,
+    Enemy,
+    Healing,
+    Background,
+    OutOfBounds,
+    Lifetime,
+    ParticleEmitter,
+    Particle,
+    Tween,
+    Fireball,
+    PowerUp,
+    ActiveEffects,
+    Animation,
+    SpriteTransform,
+    RigidBody,
+    MapTile,
+);
+
+fn hitbox_color(name: HitboxName) -> Color {
+    match name {
+        HitboxName::Body => Color::GREEN,
+        HitboxName::Head => Color::BLUE,
+        HitboxName::Feet => Color::ORANGE,
+        HitboxName::Hurtbox => Color::RED,
+    }
+}
+
+/// Hitboxes, velocities and entity details drawn over the scene, toggled with F3. Only
+/// compiled in debug builds or with the `dev-tools` feature.
+pub struct DebugOverlay {
+    pub enabled: bool,
+    cache: LabelCache,
+}
+
+impl Default for DebugOverlay {
+    fn default() -> DebugOverlay {
+        DebugOverlay {
+            enabled: false,
+            cache: LabelCache::new(512),
+        }
+    }
+}
+
+impl DebugOverlay {
+    pub fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+    }
+
+    pub fn draw(
+        &mut self,
+        world: &World,
+        window: &mut Window,
+        font: &Rc<RefCell<Asset<Font>>>,
+        world_view: View,
+        screen_view: View,
+    ) -> Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+        let camera = world.read_resource::<Camera>();
+        let view_area = camera.view_area();
+        let entities = world.entities();
+        let positions = world.read_storage::<Position>();
+        // Tiles and particles would bury everything else in text.
+        let (tiles, particles) = (
+            world.read_storage::<MapTile>(),
+            world.read_storage::<Particle>(),
+        );
+        let visible: Vec<(Entity, Vector)> = (&entities, &positions, !&tiles, !&particles)
+            .join()
+            .filter(|(_, position, _, _)| view_area.contains(position.position))
+            .map(|(entity, position, _, _)| (entity, position.position))
+            .collect();
+
+        window.flush()?;
+        window.set_view(world_view);
+        draw_shapes(
+            window,
+            &positions,
+            &world.read_storage(),
+            &world.read_storage(),
+        );
+        window.flush()?;
+        window.set_view(screen_view);
+
+        let cache = &mut self.cache;
+        let style = FontStyle::new(TEXT_SIZE, Color::BLACK);
+        font.borrow_mut().execute(|font| {
+            let mut text_at = |text: &str, top_left: Vector| -> Result<()> {
+                let image = cache.get_or_render(text, &style, || font.render(text, &style))?;
+                window.draw(&image.area().translate(top_left), Img(image));
+                Ok(())
+            };
+            for &(entity, position) in &visible {
+                let top_left = camera.to_screen(position);
+                text_at(
+                    &format!("#{} gen {}", entity.id(), entity.gen().id()),
+                    top_left,
+                )?;
+                for (line, name) in component_names(world, entity).iter().enumerate() {
+                    text_at(
+                        name,
+                        top_left + Vector::new(0.0, (line + 1) as f32 * LINE_HEIGHT),
+                    )?;
+                }
+            }
+            let counts = component_counts(world);
+            let left = camera.viewport.x - 160.0;
+            text_at(
+                &format!("Entities: {}", entities.join().count()),
+                Vector::new(left, 40.0),
+            )?;
+            for (line, (name, count)) in counts.iter().enumerate() {
+                text_at(
+                    &format!("{}: {}", name, count),
+                    Vector::new(left, 40.0 + (line + 1) as f32 * LINE_HEIGHT),
+                )?;
+            }
+            Ok(())
+        })
+    }
+}
+
+fn draw_shapes(
+    window: &mut Window,
+    positions: &ReadStorage<Position>,
+    colliders: &ReadStorage<Collider>,
+    velocities: &ReadStorage<Velocity>,
+) {
+    for (position, collider) in (positions, colliders).join() {
+        for hitbox in &collider.hitboxes {
+            let color = hitbox_color(hitbox.name);
+            match hitbox.shape_at(position.position) {
+                HitboxArea::Circle { center, radius } => {
+                    window.draw(&Circle::new(center, radius), Col(color.with_alpha(0.4)));
+                }
+                HitboxArea::Polygon(points) => {
+                    for i in 1..points.len().saturating_sub(1) {
+                        window.draw(
+                            &Triangle::new(points[0], points[i], points[i + 1]),
+                            Col(color.with_alpha(0.4)),
+                        );
+                    }
+                }
+            }
+            let outline = hitbox.area(position.position);
+            draw_outline(window, outline, color);
+        }
+    }
+    for (position, velocity) in (positions, velocities).join() {
+        if velocity.velocity.len2() > 0.0 {
+            window.draw(
+                &Line::new(
+                    position.position,
+                    position.position + velocity.velocity * VELOCITY_SCALE,
+                )
+                .with_thickness(2.0),
+                Col(Color::MAGENTA),
+            );
+        }
+    }
+}
+
+fn draw_outline(window: &mut Window, area: Rectangle, color: Color) {
+    let corners = [
+        area.top_left(),
+        area.top_left() + Vector::new(area.width(), 0.0),
+        area.top_left() + area.size(),
+        area.top_left() + Vector::new(0.0, area.height()),
+    ];
+    for i in 0..corners.len() {
+        window.draw(
+            &Line::new(corners[i], corners[(i + 1) % corners.len()]),
+            Col(color),
+        );
+    }
+}
@@ -1,5 +1,19 @@
-use crate::component::{
-    Boss, CalculateOutOfBounds, ChangeSprite, Enemy, Fireball, Position, Render, Shooter, Velocity,
+use crate::{
+    animation::{still_clips, AnimationClips},
+    bounds::{DespawnPolicy, WorldBounds},
+    camera::Camera,
+    collider::Hitbox,
+    collision::{CollisionLayerConfig, CollisionLayerName},
+    component::{
+        Animation, Boss, ChangeSprite, Collider, CollisionLayer, ContinuousCollision, Enemy,
+        Facing, Fireball, OutOfBounds, ParticleEmitter, Position, Render, RigidBody, Shooter,
+        SpriteTransform, Tween, Velocity,
+    },
+    entity_factory::PrefabsConfig,
+    particles::ParticleEmitterConfig,
+    resources::RenderLayer,
+    sprite::{intern_sprite, SpriteTable},
+    tween::{Easing, TweenConfig, TweenProperty},
 };
 use serde::{Deserialize, Serialize};
 
@@ -8,26 +22,10 @@ use specs::{
     World,
 };
 
-use quicksilver::geom::{Rectangle, Shape, Vector};
+use quicksilver::geom::Vector;
 
 use rand::{thread_rng, Rng};
 
-const ENEMY_HEAD_HEIGHT: f32 = 10.;
-
-pub fn get_enemy_head_body_area(self_area: Rectangle, position: Vector) -> (Rectangle, Rectangle) {
-    let self_area = self_area.with_center(position);
-    (
-        Rectangle::new(
-            self_area.top_left(),
-            Vector::new(self_area.width(), ENEMY_HEAD_HEIGHT),
-        ),
-        Rectangle::new(
-            self_area.top_left() + Vector::new(0., 10),
-            Vector::new(self_area.width(), self_area.height() - ENEMY_HEAD_HEIGHT),
-        ),
-    )
-}
-
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct EnemyConfig {
     pub sprite: String,
@@ -35,6 +33,28 @@ pub struct EnemyConfig {
     pub velocity: Vector,
     pub score: i32,
     pub shooter_config: Option<ShooterConfig>,
+    pub gravity_scale: Option<f32>,
+    /// Gap between the collider and the ground when entering from a side.
+    #[serde(default)]
+    pub altitude: f32,
+    #[serde(default = "default_enemy_collider")]
+    pub collider: Vec<Hitbox>,
+    #[serde(default = "default_enemy_layer")]
+    pub collision_layer: CollisionLayerConfig,
+    #[serde(default)]
+    pub despawn: DespawnPolicy,
+    /// Clips by state, single-image clips of `sprite` when empty.
+    #[serde(default)]
+    pub animations: AnimationClips,
+    #[serde(default)]
+    pub transform: Option<SpriteTransform>,
+    #[serde(default)]
+    pub kind: Option<EnemyType>,
+    #[serde(default)]
+    pub layer: RenderLayer,
+    /// Order inside the layer, higher is drawn later.
+    #[serde(default)]
+    pub z: f32,
 }
 
 #[derive(Serialize, Deserialize, Debug, Copy, Clone)]
@@ -46,9 +66,12 @@ pub enum EnemyType {
 }
 
 pub fn create_enemy(world: &mut World, config: EnemyConfig) {
+    let sprite = intern_sprite(world, &config.sprite);
     let mut builder = world
         .create_entity()
-        .with(CalculateOutOfBounds)
+        .with(OutOfBounds {
+            policy: config.despawn,
+        })
         .with(Position {
             position: config.position,
         })
@@ -56,74 +79,196 @@ pub fn create_enemy(world: &mut World, config: EnemyConfig) {
             velocity: config.velocity,
         })
         .with(Render {
-            sprite: config.sprite.clone(),
-            bounding_box: None,
+            sprite,
+            layer: config.layer,
+            z: config.z,
+        })
+        .with(Collider {
+            hitboxes: config.collider,
         })
+        .with(CollisionLayer::new(&config.collision_layer))
         .with(Enemy {
             score: config.score,
+            kind: config.kind,
         });
+    if let Some(gravity_scale) = config.gravity_scale {
+        builder = builder.with(RigidBody {
+            gravity_scale,
+            grounded: false,
+        });
+    }
+    let animations = if config.animations.is_empty() {
+        still_clips(&config.sprite)
+    } else {
+        config.animations
+    };
+    builder = builder.with(Animation::new(animations));
+    if let Some(transform) = config.transform {
+        builder = builder.with(transform);
+    }
     if let Some(shooter_config) = config.shooter_config {
         builder = builder.with(Shooter {
-            projectile_sprite: shooter_config.projectile_sprite.clone(),
+            projectile_sprite: shooter_config.projectile_sprite,
+            projectile_collider: shooter_config.projectile_collider,
+            projectile_layer: shooter_config.projectile_layer,
+            projectile_render_layer: shooter_config.projectile_render_layer,
+            fast_projectiles: shooter_config.fast_projectiles,
+            projectile_trail: shooter_config.projectile_trail,
             maximum_fireballs: shooter_config.maximum_projectiles,
             fireball_amount: 0,
             coefficient: shooter_config.projectile_coefficient,
+            active: true,
         });
     }
     builder.build();
 }
 
-pub fn create_walker(world: &mut World) {
-    let config = if rand::random() {
-        EnemyConfig {
-            sprite: "andador".to_string(),
-            position: Vector::new(850.0, 432.0),
-            velocity: Vector::new(-125.0, 0.0),
-            score: 100,
-            shooter_config: None,
-        }
-    } else {
-        EnemyConfig {
-            sprite: "andador_flipped".to_string(),
-            position: Vector::new(-50.0, 432.0),
-            velocity: Vector::new(125.0, 0.0),
-            score: 100,
-            shooter_config: None,
-        }
-    };
-    create_enemy(world, config);
+pub fn walker_config() -> EnemyConfig {
+    EnemyConfig {
+        sprite: "andador".to_string(),
+        position: Vector::ZERO,
+        velocity: Vector::new(-125.0, 0.0),
+        score: 100,
+        shooter_config: None,
+        gravity_scale: Some(1.0),
+        altitude: 3.0,
+        collider: crate::collider::head_body(Vector::new(56.0, 50.0), 10.0),
+        collision_layer: default_enemy_layer(),
+        despawn: DespawnPolicy::Offscreen,
+        animations: still_clips("andador"),
+        transform: Some(SpriteTransform::facing(Facing::Left)),
+        kind: Some(EnemyType::Walker),
+        layer: RenderLayer::World,
+        z: 0.0,
+    }
 }
 
-pub fn create_shooter(world: &mut World) {
-    let config = EnemyConfig {
+pub fn shooter_config() -> EnemyConfig {
+    EnemyConfig {
         sprite: "atirador".to_string(),
-        position: Vector::new(850.0, 433.5),
+        position: Vector::ZERO,
         velocity: Vector::new(-125.0, 0.0),
         score: 200,
         shooter_config: Some(ShooterConfig {
             projectile_sprite: "tiro".to_string(),
             maximum_projectiles: 2,
             projectile_coefficient: (0.175, 0.0),
+            projectile_collider: default_projectile_collider(),
+            projectile_layer: default_projectile_layer(),
+            projectile_render_layer: RenderLayer::Projectiles,
+            fast_projectiles: true,
+            projectile_trail: default_projectile_trail(),
         }),
-    };
-    create_enemy(world, config);
+        gravity_scale: Some(1.0),
+        altitude: 0.0,
+        collider: crate::collider::head_body(Vector::new(56.0, 53.0), 10.0),
+        collision_layer: default_enemy_layer(),
+        despawn: DespawnPolicy::Offscreen,
+        animations: still_clips("atirador"),
+        transform: None,
+        kind: Some(EnemyType::Shooter),
+        layer: RenderLayer::World,
+        z: 0.0,
+    }
 }
 
-pub fn create_flyer(world: &mut World) {
-    let config = EnemyConfig {
+pub fn flyer_config() -> EnemyConfig {
+    EnemyConfig {
         sprite: "alma".to_string(),
-        position: Vector::new(850.0, 400.0),
+        position: Vector::ZERO,
         velocity: Vector::new(-150.0, 0.0),
         score: 200,
         shooter_config: Some(ShooterConfig {
             projectile_sprite: "tiro".to_string(),
             maximum_projectiles: 1,
             projectile_coefficient: (0.250, 0.0),
+            projectile_collider: default_projectile_collider(),
+            projectile_layer: default_projectile_layer(),
+            projectile_render_layer: RenderLayer::Projectiles,
+            fast_projectiles: true,
+            projectile_trail: default_projectile_trail(),
         }),
+        gravity_scale: None,
+        altitude: 36.0,
+        collider: crate::collider::head_body(Vector::new(48.0, 48.0), 10.0),
+        collision_layer: default_enemy_layer(),
+        despawn: DespawnPolicy::Offscreen,
+        animations: still_clips("alma"),
+        transform: None,
+        kind: Some(EnemyType::Flyer),
+        layer: RenderLayer::World,
+        z: 0.0,
+    }
+}
+
+/// Places a prefab `altitude` above the ground just past the right edge, or mirrored past the
+/// left one.
+fn enter_from_side(world: &World, config: EnemyConfig, left: bool) -> EnemyConfig {
+    let bounds = *world.read_resource::<WorldBounds>();
+    let view = world.read_resource::<Camera>().view_area();
+    let x = bounds.side_spawn_x(&view, left);
+    let bottom = config
+        .collider
+        .iter()
+        .map(|hitbox| {
+            let area = hitbox.area(Vector::ZERO);
+            area.pos.y + area.size.y
+        })
+        .fold(0.0, f32::max);
+    let y = crate::physics::ground_at(world, x) - config.altitude - bottom;
+    let velocity_x = if left {
+        -config.velocity.x
+    } else {
+        config.velocity.x
     };
+    EnemyConfig {
+        position: Vector::new(x, y),
+        velocity: Vector::new(velocity_x, config.velocity.y),
+        ..config
+    }
+}
+
+fn prefab(world: &World, enemy_type: EnemyType) -> Option<EnemyConfig> {
+    let prefabs = world.read_resource::<PrefabsConfig>();
+    match enemy_type {
+        EnemyType::Walker => Some(prefabs.walker.clone()),
+        EnemyType::Shooter => Some(prefabs.shooter.clone()),
+        EnemyType::Flyer => Some(prefabs.flyer.clone()),
+        EnemyType::FireballShower => None,
+    }
+}
+
+pub fn create_walker(world: &mut World) {
+    let config = prefab(world, EnemyType::Walker).unwrap();
+    let config = enter_from_side(world, config, rand::random());
+    create_enemy(world, config);
+}
+
+pub fn create_shooter(world: &mut World) {
+    let config = prefab(world, EnemyType::Shooter).unwrap();
+    let config = enter_from_side(world, config, false);
+    create_enemy(world, config);
+}
+
+pub fn create_flyer(world: &mut World) {
+    let config = prefab(world, EnemyType::Flyer).unwrap();
+    let config = enter_from_side(world, config, false);
     create_enemy(world, config);
 }
 
+pub fn create_enemy_at(world: &mut World, enemy_type: EnemyType, position: Vector) {
+    match prefab(world, enemy_type) {
+        Some(config) => create_enemy(world, EnemyConfig { position, ..config }),
+        None => {
+            let config = world
+                .read_resource::<PrefabsConfig>()
+                .fireball_shower
+                .clone();
+            create_fireball_shower(world, config);
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(default)]
 pub struct BossConfig {
@@ -133,6 +278,26 @@ pub struct BossConfig {
     pub lives: i32,
     pub normal_lives: i32,
     pub shooter_config: ShooterConfig,
+    pub collider: Vec<Hitbox>,
+    pub collision_layer: CollisionLayerConfig,
+    /// Clips by state, single-image clips of `sprite` when empty.
+    pub animations: AnimationClips,
+    pub layer: RenderLayer,
+    /// Order inside the layer, higher is drawn later.
+    pub z: f32,
+    /// Played when the boss appears; it spawns back by the `Move` steps to end at `position`.
+    #[serde(default = "default_boss_entrance")]
+    pub entrance: Option<TweenConfig>,
+}
+
+pub fn default_boss_entrance() -> Option<TweenConfig> {
+    Some(TweenConfig::once(
+        TweenProperty::Move {
+            by: Vector::new(-300.0, 0.0),
+        },
+        1500,
+        Easing::CubicOut,
+    ))
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -140,6 +305,48 @@ pub struct ShooterConfig {
     pub projectile_sprite: String,
     pub maximum_projectiles: i32,
     pub projectile_coefficient: (f32, f32),
+    #[serde(default = "default_projectile_collider")]
+    pub projectile_collider: Vec<Hitbox>,
+    #[serde(default = "default_projectile_layer")]
+    pub projectile_layer: CollisionLayerConfig,
+    #[serde(default = "default_projectile_render_layer")]
+    pub projectile_render_layer: RenderLayer,
+    /// Projectiles use continuous collision so they can't pass through targets between ticks.
+    #[serde(default = "default_fast_projectiles")]
+    pub fast_projectiles: bool,
+    #[serde(default = "default_projectile_trail")]
+    pub projectile_trail: Option<ParticleEmitterConfig>,
+}
+
+pub fn default_projectile_trail() -> Option<ParticleEmitterConfig> {
+    Some(ParticleEmitterConfig::trail("tiro"))
+}
+
+pub fn default_projectile_render_layer() -> RenderLayer {
+    RenderLayer::Projectiles
+}
+
+pub fn default_projectile_collider() -> Vec<Hitbox> {
+    crate::collider::body(Vector::new(20.0, 12.0))
+}
+
+pub fn default_fast_projectiles() -> bool {
+    true
+}
+
+pub fn default_enemy_collider() -> Vec<Hitbox> {
+    crate::collider::head_body(Vector::new(48.0, 48.0), 10.0)
+}
+
+pub fn default_enemy_layer() -> CollisionLayerConfig {
+    CollisionLayerConfig::new(&[CollisionLayerName::Enemy], &[CollisionLayerName::Hero])
+}
+
+pub fn default_projectile_layer() -> CollisionLayerConfig {
+    CollisionLayerConfig::new(
+        &[CollisionLayerName::Projectile],
+        &[CollisionLayerName::Hero, CollisionLayerName::Wall],
+    )
 }
 
 impl Default for BossConfig {
@@ -154,37 +361,76 @@ impl Default for BossConfig {
                 projectile_sprite: "tiro".to_string(),
                 maximum_projectiles: 2,
                 projectile_coefficient: (0.075, -0.05),
+                projectile_collider: default_projectile_collider(),
+                projectile_layer: default_projectile_layer(),
+                projectile_render_layer: RenderLayer::Projectiles,
+                fast_projectiles: true,
+                projectile_trail: default_projectile_trail(),
             },
+            collider: crate::collider::head_body(Vector::new(110.0, 120.0), 10.0),
+            collision_layer: default_enemy_layer(),
+            animations: still_clips("chefe"),
+            layer: RenderLayer::World,
+            z: 0.0,
+            entrance: default_boss_entrance(),
         }
     }
 }
 
 pub fn create_boss(world: &mut World, config: BossConfig) {
-    world
+    let sprite = intern_sprite(world, &config.sprite);
+    let angry_sprite = intern_sprite(world, &config.angry_sprite);
+    let mut position = config.position;
+    if let Some(entrance) = &config.entrance {
+        position -= entrance.displacement();
+    }
+    let mut builder = world
         .create_entity()
         .with(Boss {
             lives: config.lives,
             normal_lives: config.normal_lives,
         })
-        .with(Position {
-            position: config.position,
-        })
+        .with(Position { position })
         .with(Render {
-            sprite: config.sprite.clone(),
-            bounding_box: None,
+            sprite,
+            layer: config.layer,
+            z: config.z,
+        })
+        .with(Collider {
+            hitboxes: config.collider,
+        })
+        .with(CollisionLayer::new(&config.collision_layer))
+        .with(Enemy {
+            score: 300,
+            kind: None,
         })
-        .with(Enemy { score: 300 })
         .with(ChangeSprite {
-            new_sprite: config.angry_sprite.clone(),
+            new_sprite: angry_sprite,
             do_change: false,
         })
         .with(Shooter {
-            projectile_sprite: config.shooter_config.projectile_sprite.clone(),
+            projectile_sprite: config.shooter_config.projectile_sprite,
+            projectile_collider: config.shooter_config.projectile_collider,
+            projectile_layer: config.shooter_config.projectile_layer,
+            projectile_render_layer: config.shooter_config.projectile_render_layer,
+            fast_projectiles: config.shooter_config.fast_projectiles,
+            projectile_trail: config.shooter_config.projectile_trail,
             maximum_fireballs: config.shooter_config.maximum_projectiles,
             fireball_amount: 0,
             coefficient: config.shooter_config.projectile_coefficient,
-        })
-        .build();
+            // Holds fire until the entrance is over.
+            active: config.entrance.is_none(),
+        });
+    let animations = if config.animations.is_empty() {
+        still_clips(&config.sprite)
+    } else {
+        config.animations
+    };
+    builder = builder.with(Animation::new(animations));
+    if let Some(entrance) = config.entrance {
+        builder = builder.with(Tween::new(entrance));
+    }
+    builder.build();
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -192,52 +438,105 @@ pub struct FireballConfig {
     pub sprite: String,
     pub position: Vector,
     pub velocity: Vector,
+    pub collider: Vec<Hitbox>,
+    pub collision_layer: CollisionLayerConfig,
+    pub fast: bool,
+    pub trail: Option<ParticleEmitterConfig>,
+    pub layer: RenderLayer,
+    pub z: f32,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(default)]
 pub struct FireballShowerConfig {
     pub sprite: String,
+    /// Falling speed, each fireball varying it by up to 10.
     pub y_velocity: f32,
+    pub collider: Vec<Hitbox>,
+    pub collision_layer: CollisionLayerConfig,
+    pub trail: Option<ParticleEmitterConfig>,
+    pub layer: RenderLayer,
+    /// Order inside the layer, higher is drawn later.
+    pub z: f32,
 }
 
 impl Default for FireballShowerConfig {
     fn default() -> FireballShowerConfig {
         FireballShowerConfig {
             sprite: "fogo".to_string(),
-            y_velocity: -100.0,
+            y_velocity: 250.0,
+            collider: crate::collider::circle(12.0),
+            collision_layer: default_projectile_layer(),
+            trail: Some(ParticleEmitterConfig::trail("fogo")),
+            layer: RenderLayer::Projectiles,
+            z: 0.0,
         }
     }
 }
 
 pub fn create_fireball_shower(world: &mut World, config: FireballShowerConfig) {
+    let bounds = *world.read_resource::<WorldBounds>();
     let mut rng = thread_rng();
-    let x_init: i32 = rng.gen_range(0, 100);
-    let x_end: i32 = rng.gen_range(810, 900);
+    let area = world.read_resource::<Camera>().view_area();
+    let y = area.pos.y - bounds.spawn_margin;
+    let x_init = area.pos.x as i32 + rng.gen_range(0, 100);
+    let x_end = (area.pos.x + area.size.x) as i32 + rng.gen_range(10, 100);
     let step: usize = rng.gen_range(90, 120);
+    let mut sprites = world.write_resource::<SpriteTable>();
     for x in (x_init..x_end).step_by(step) {
         let fireball_config = FireballConfig {
             sprite: config.sprite.clone(),
-            position: Vector::new(x as f32, config.y_velocity),
-            velocity: Vector::new(0.0, 250.0 + rng.gen_range(-10.0, 10.0)),
+            position: Vector::new(x as f32, y),
+            velocity: Vector::new(0.0, config.y_velocity + rng.gen_range(-10.0, 10.0)),
+            collider: config.collider.clone(),
+            collision_layer: config.collision_layer.clone(),
+            fast: false,
+            trail: config.trail.clone(),
+            layer: config.layer,
+            z: config.z,
         };
-        create_fireball(world.create_entity(), None, fireball_config);
+        create_fireball(
+            world.create_entity_unchecked(),
+            &mut sprites,
+            None,
+            fireball_config,
+        );
     }
 }
 
-pub fn create_fireball<T: Builder>(builder: T, owner_id: Option<Index>, config: FireballConfig) {
-    builder
+/// Interns `config.sprite` in `sprites`, so systems can build fireballs lazily.
+pub fn create_fireball<T: Builder>(
+    builder: T,
+    sprites: &mut SpriteTable,
+    owner_id: Option<Index>,
+    config: FireballConfig,
+) {
+    let sprite = sprites.intern(&config.sprite);
+    let mut builder = builder
         .with(Fireball { owner_id })
-        .with(CalculateOutOfBounds)
+        .with(OutOfBounds {
+            policy: DespawnPolicy::Offscreen,
+        })
         .with(Render {
-            sprite: config.sprite,
-            bounding_box: None,
+            sprite,
+            layer: config.layer,
+            z: config.z,
+        })
+        .with(Collider {
+            hitboxes: config.collider,
         })
+        .with(CollisionLayer::new(&config.collision_layer))
         .with(Position {
             position: config.position,
         })
         .with(Velocity {
             velocity: config.velocity,
-        })
-        .build();
+        });
+    if config.fast {
+        builder = builder.with(ContinuousCollision::default());
+    }
+    if let Some(trail) = config.trail {
+        builder = builder.with(ParticleEmitter::new(trail));
+    }
+    builder.build();
 }
@@ -1,8 +1,11 @@
 use crate::{
-    enemy::{EnemyType, FireballShowerConfig},
+    bounds::WorldBounds,
+    camera::Camera,
+    enemy::{EnemyConfig, EnemyType, FireballShowerConfig},
     healing::HealingConfig,
+    power_up::PowerUpConfig,
 };
-use quicksilver::Result;
+use quicksilver::{geom::Vector, Result};
 use rand::{thread_rng, Rng};
 use serde::{Deserialize, Serialize};
 use specs::World;
@@ -13,12 +16,39 @@ pub enum FactoryType {
     Random,
 }
 
+/// Templates the factory, the map objects and the console spawn from.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct PrefabsConfig {
+    pub walker: EnemyConfig,
+    pub shooter: EnemyConfig,
+    pub flyer: EnemyConfig,
+    pub fireball_shower: FireballShowerConfig,
+    pub healing: HealingConfig,
+}
+
+impl Default for PrefabsConfig {
+    fn default() -> PrefabsConfig {
+        PrefabsConfig {
+            walker: crate::enemy::walker_config(),
+            shooter: crate::enemy::shooter_config(),
+            flyer: crate::enemy::flyer_config(),
+            fireball_shower: FireballShowerConfig::default(),
+            healing: HealingConfig::default(),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(default)]
 pub struct EntityFactoryConfig {
     pub factory_type: FactoryType,
     pub enemy_types: Vec<EnemyType>,
+    /// Enemies between two potions, `None` or 0 for no potions.
     pub healing_interval: Option<i32>,
+    /// Enemies between two power-ups, `None` or 0 for no power-ups.
+    pub power_up_interval: Option<i32>,
+    pub power_ups: Vec<PowerUpConfig>,
 }
 
 impl Default for EntityFactoryConfig {
@@ -27,6 +57,8 @@ impl Default for EntityFactoryConfig {
             factory_type: FactoryType::Fixed,
             enemy_types: vec![EnemyType::Walker, EnemyType::Shooter],
             healing_interval: Some(3),
+            power_up_interval: Some(4),
+            power_ups: crate::power_up::default_power_ups(),
         }
     }
 }
@@ -35,6 +67,8 @@ pub struct EntityFactory {
     factory_type: FactoryType,
     enemy_types: Vec<EnemyType>,
     healing_interval: Option<i32>,
+    power_up_interval: Option<i32>,
+    power_ups: Vec<PowerUpConfig>,
     counter: i32,
 }
 
@@ -43,7 +77,9 @@ impl EntityFactory {
         Ok(EntityFactory {
             factory_type: config.factory_type,
             enemy_types: config.enemy_types,
-            healing_interval: config.healing_interval,
+            healing_interval: enabled_interval(config.healing_interval),
+            power_up_interval: enabled_interval(config.power_up_interval),
+            power_ups: config.power_ups,
             counter: 0,
         })
     }
@@ -58,13 +94,60 @@ impl EntityFactory {
             EnemyType::Shooter => crate::enemy::create_shooter(world),
             EnemyType::Flyer => crate::enemy::create_flyer(world),
             EnemyType::FireballShower => {
-                crate::enemy::create_fireball_shower(world, FireballShowerConfig::default())
+                crate::enemy::create_enemy_at(world, EnemyType::FireballShower, Vector::ZERO)
             }
         };
-        if self.healing_interval.is_some() && self.counter % self.healing_interval.unwrap() == 0 {
-            crate::healing::create_healing_potion(world, HealingConfig::default());
+        if let Some(interval) = self.healing_interval {
+            if self.counter % interval == 0 {
+                let healing = world.read_resource::<PrefabsConfig>().healing.clone();
+                let bounds = *world.read_resource::<WorldBounds>();
+                let view = world.read_resource::<Camera>().view_area();
+                crate::healing::create_healing_potion(
+                    world,
+                    HealingConfig {
+                        position: bounds.drop_position(&view),
+                        ..healing
+                    },
+                );
+            }
+        }
+        if let Some(interval) = self.power_up_interval {
+            if self.counter > 0 && self.counter % interval == 0 && !self.power_ups.is_empty() {
+                let power_up = thread_rng().gen_range(0, self.power_ups.len());
+                crate::power_up::create_power_up(world, self.power_ups[power_up].clone());
+            }
         }
         self.counter += 1;
         Ok(())
     }
 }
+
+/// Intervals below 1 can't be used as divisors, they turn the spawns off.
+fn enabled_interval(interval: Option<i32>) -> Option<i32> {
+    interval.filter(|interval| *interval > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zero_and_negative_intervals_are_disabled() {
+        assert_eq!(enabled_interval(Some(0)), None);
+        assert_eq!(enabled_interval(Some(-2)), None);
+        assert_eq!(enabled_interval(Some(3)), Some(3));
+        assert_eq!(enabled_interval(None), None);
+    }
+
+    #[test]
+    fn factory_drops_zero_intervals() {
+        let factory = EntityFactory::new(EntityFactoryConfig {
+            healing_interval: Some(0),
+            power_up_interval: Some(0),
+            ..EntityFactoryConfig::default()
+        })
+        .unwrap();
+        assert_eq!(factory.healing_interval, None);
+        assert_eq!(factory.power_up_interval, None);
+    }
+}
@@ -1,4 +1,4 @@
-use crate::scene::{Scene, SceneConfig};
+use crate::scene::{Scene, SceneConfig, SCENE_CONFIG_PATH};
 use log::Level;
 use quicksilver::prelude::*;
 use std::path::{Path, PathBuf};
@@ -10,7 +10,7 @@ struct Game {
 impl State for Game {
     fn new() -> Result<Self> {
         Ok(Game {
-            scene: Asset::new(create_scene("scene.json")),
+            scene: Asset::new(create_scene(SCENE_CONFIG_PATH)),
         })
     }
 
@@ -1,33 +1,68 @@
-use crate::component::{CalculateOutOfBounds, Healing, Position, Render, Velocity};
+use crate::{
+    bounds::DespawnPolicy,
+    collider::Hitbox,
+    collision::{CollisionLayerConfig, CollisionLayerName},
+    component::{
+        Collider, CollisionLayer, Healing, Lifetime, OutOfBounds, Position, Render, RigidBody,
+        SpriteTransform, Tween, Velocity,
+    },
+    resources::RenderLayer,
+    sprite::intern_sprite,
+    tween::TweenConfig,
+};
 use quicksilver::geom::Vector;
-use rand::{thread_rng, Rng};
 use serde::{Deserialize, Serialize};
 use specs::{Builder, Entity, World};
+use std::time::Duration;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(default)]
 pub struct HealingConfig {
     pub sprite: String,
+    /// Where map objects and the console place it; the factory drops potions from above.
     pub position: Vector,
     pub velocity: Vector,
     pub score: i32,
+    pub gravity_scale: Option<f32>,
+    pub collider: Vec<Hitbox>,
+    pub collision_layer: CollisionLayerConfig,
+    pub despawn: DespawnPolicy,
+    pub lifetime_ms: Option<u64>,
+    pub tween: Option<TweenConfig>,
+    pub layer: RenderLayer,
+    /// Order inside the layer, higher is drawn later.
+    pub z: f32,
 }
 
 impl Default for HealingConfig {
     fn default() -> HealingConfig {
         HealingConfig {
             sprite: "potion".to_string(),
-            position: Vector::new(thread_rng().gen_range(50.0, 700.0), -100.0),
+            position: Vector::ZERO,
             velocity: Vector::new(0.0, 250.0),
             score: 50,
+            gravity_scale: Some(1.0),
+            collider: crate::collider::body(Vector::new(24.0, 32.0)),
+            collision_layer: CollisionLayerConfig::new(
+                &[CollisionLayerName::Pickup],
+                &[CollisionLayerName::Hero],
+            ),
+            despawn: DespawnPolicy::Offscreen,
+            lifetime_ms: Some(10000),
+            tween: Some(TweenConfig::bob(6.0, 600)),
+            layer: RenderLayer::World,
+            z: 0.0,
         }
     }
 }
 
 pub fn create_healing_potion(world: &mut World, config: HealingConfig) -> Entity {
-    world
+    let sprite = intern_sprite(world, &config.sprite);
+    let mut builder = world
         .create_entity()
-        .with(CalculateOutOfBounds)
+        .with(OutOfBounds {
+            policy: config.despawn,
+        })
         .with(Position {
             position: config.position,
         })
@@ -35,11 +70,32 @@ pub fn create_healing_potion(world: &mut World, config: HealingConfig) -> Entity
             velocity: config.velocity,
         })
         .with(Render {
-            sprite: config.sprite,
-            bounding_box: None,
+            sprite,
+            layer: config.layer,
+            z: config.z,
+        })
+        .with(Collider {
+            hitboxes: config.collider,
         })
+        .with(CollisionLayer::new(&config.collision_layer))
         .with(Healing {
             score: config.score,
-        })
-        .build()
+        });
+    if let Some(gravity_scale) = config.gravity_scale {
+        builder = builder.with(RigidBody {
+            gravity_scale,
+            grounded: false,
+        });
+    }
+    if let Some(lifetime_ms) = config.lifetime_ms {
+        builder = builder.with(Lifetime {
+            remaining: Duration::from_millis(lifetime_ms),
+        });
+    }
+    if let Some(tween) = config.tween {
+        builder = builder
+            .with(Tween::new(tween))
+            .with(SpriteTransform::default());
+    }
+    builder.build()
 }
@@ -1,31 +1,35 @@
-use crate::component::{CalculateOutOfBounds, Hero, Position, Render, Velocity};
-use quicksilver::geom::{Rectangle, Shape, Vector};
+use crate::{
+    animation::{still_clips, AnimationClips},
+    bounds::DespawnPolicy,
+    collider::Hitbox,
+    collision::{CollisionLayerConfig, CollisionLayerName},
+    component::{
+        ActiveEffects, Animation, Collider, CollisionLayer, Hero, OutOfBounds, Position, Render,
+        RigidBody, SpriteTransform, Velocity,
+    },
+    resources::RenderLayer,
+    sprite::intern_sprite,
+};
+use quicksilver::geom::Vector;
 use serde::{Deserialize, Serialize};
 use specs::{Builder, Entity, World};
 use std::time::Duration;
 
-const HERO_FEET_HEIGHT: f32 = 10.0;
-
-pub fn get_hero_body_feet_area(self_area: Rectangle, position: Vector) -> (Rectangle, Rectangle) {
-    let self_area = self_area.with_center(position);
-    (
-        Rectangle::new(
-            self_area.top_left(),
-            Vector::new(self_area.width(), self_area.height() - HERO_FEET_HEIGHT),
-        ),
-        Rectangle::new(
-            self_area.top_left() + Vector::new(0., self_area.height() - HERO_FEET_HEIGHT),
-            Vector::new(self_area.width(), HERO_FEET_HEIGHT),
-        ),
-    )
-}
-
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(default)]
 pub struct HeroConfig {
     pub sprite: String,
     pub position: Vector,
     pub lives: i32,
+    pub collider: Vec<Hitbox>,
+    pub collision_layer: CollisionLayerConfig,
+    pub despawn: DespawnPolicy,
+    /// Clips by state, single-image clips of `sprite` when empty.
+    pub animations: AnimationClips,
+    pub transform: Option<SpriteTransform>,
+    pub layer: RenderLayer,
+    /// Order inside the layer, higher is drawn later.
+    pub z: f32,
 }
 
 impl Default for HeroConfig {
@@ -34,12 +38,27 @@ impl Default for HeroConfig {
             sprite: "heroi".to_string(),
             position: Vector::new(425.0, 425.0),
             lives: 5,
+            collider: crate::collider::body_feet(Vector::new(44.0, 64.0), 10.0),
+            collision_layer: CollisionLayerConfig::new(
+                &[CollisionLayerName::Hero],
+                &[
+                    CollisionLayerName::Enemy,
+                    CollisionLayerName::Pickup,
+                    CollisionLayerName::Projectile,
+                ],
+            ),
+            despawn: DespawnPolicy::Clamp,
+            animations: still_clips("heroi"),
+            transform: None,
+            layer: RenderLayer::World,
+            z: 1.0,
         }
     }
 }
 
 pub fn create_hero(world: &mut World, config: HeroConfig) -> Entity {
-    world
+    let sprite = intern_sprite(world, &config.sprite);
+    let mut builder = world
         .create_entity()
         .with(Hero {
             lives: config.lives,
@@ -48,8 +67,18 @@ pub fn create_hero(world: &mut World, config: HeroConfig) -> Entity {
             render: true,
             reset_position: false,
             blink_timer: Duration::from_millis(0),
+            air_jumps: 0,
+            jump_pressed: false,
+            invincible: false,
+        })
+        .with(ActiveEffects::default())
+        .with(RigidBody {
+            gravity_scale: 1.0,
+            grounded: false,
+        })
+        .with(OutOfBounds {
+            policy: config.despawn,
         })
-        .with(CalculateOutOfBounds)
         .with(Position {
             position: config.position,
         })
@@ -57,8 +86,22 @@ pub fn create_hero(world: &mut World, config: HeroConfig) -> Entity {
             velocity: Vector::ZERO,
         })
         .with(Render {
-            sprite: config.sprite,
-            bounding_box: None,
+            sprite,
+            layer: config.layer,
+            z: config.z,
+        })
+        .with(Collider {
+            hitboxes: config.collider,
         })
-        .build()
+        .with(CollisionLayer::new(&config.collision_layer));
+    let animations = if config.animations.is_empty() {
+        still_clips(&config.sprite)
+    } else {
+        config.animations
+    };
+    builder = builder.with(Animation::new(animations));
+    if let Some(transform) = config.transform {
+        builder = builder.with(transform);
+    }
+    builder.build()
 }
@@ -4,14 +4,15 @@ use std::time::Duration;
 use std::time::Instant as StdInstant;
 
 #[cfg(target_arch = "wasm32")]
-use stdweb::{unstable::TryInto, Value};
+use stdweb::unstable::TryInto;
 
 #[derive(Debug, Clone)]
 pub struct Instant {
     #[cfg(not(target_arch = "wasm32"))]
     instant: StdInstant,
+    /// Milliseconds from `performance.now()`, with its sub-millisecond fraction.
     #[cfg(target_arch = "wasm32")]
-    instant: Value,
+    instant: f64,
 }
 
 impl Instant {
@@ -24,10 +25,12 @@ impl Instant {
         }
         #[cfg(target_arch = "wasm32")]
         {
-            let instant: Value = js! {
+            let instant = js! {
                 return performance.now();
             };
-            Instant { instant }
+            Instant {
+                instant: instant.try_into().unwrap(),
+            }
         }
     }
 
@@ -38,10 +41,8 @@ impl Instant {
         }
         #[cfg(target_arch = "wasm32")]
         {
-            let elapsed: Value = js! {
-                return parseInt(@{&self.instant} - @{&earlier.instant});//milliseconds
-            };
-            Duration::from_millis(elapsed.try_into().unwrap())
+            let elapsed = (self.instant - earlier.instant).max(0.0);
+            Duration::from_nanos((elapsed * 1_000_000.0) as u64)
         }
     }
 }
@@ -0,0 +1,191 @@
+use quicksilver::{
+    graphics::{FontStyle, Image},
+    Result,
+};
+
+struct CachedLabel<I> {
+    /// The text, or the template of a formatted label.
+    text: String,
+    /// Value the template was filled with.
+    value: Option<String>,
+    style: FontStyle,
+    image: I,
+    last_used: u64,
+}
+
+/// Rendered label images, reused while their text and style don't change. Once `capacity`
+/// is reached the least recently used image is dropped.
+pub struct LabelCache<I = Image> {
+    entries: Vec<CachedLabel<I>>,
+    capacity: usize,
+    clock: u64,
+}
+
+impl<I> Default for LabelCache<I> {
+    fn default() -> LabelCache<I> {
+        LabelCache::new(32)
+    }
+}
+
+impl<I> LabelCache<I> {
+    pub fn new(capacity: usize) -> LabelCache<I> {
+        LabelCache {
+            entries: Vec::new(),
+            capacity: capacity.max(1),
+            clock: 0,
+        }
+    }
+
+    /// Cached image for `text`, calling `render` only when it isn't there.
+    pub fn get_or_render<F>(&mut self, text: &str, style: &FontStyle, render: F) -> Result<&I>
+    where
+        F: FnOnce() -> Result<I>,
+    {
+        self.get(text, None, style, render)
+    }
+
+    /// Cached image for `template` filled with `value`. Keyed by both, so `render` can format
+    /// the text and it only happens when the value changes.
+    pub fn get_or_format<F>(
+        &mut self,
+        template: &str,
+        value: &str,
+        style: &FontStyle,
+        render: F,
+    ) -> Result<&I>
+    where
+        F: FnOnce() -> Result<I>,
+    {
+        self.get(template, Some(value), style, render)
+    }
+
+    fn get<F>(
+        &mut self,
+        text: &str,
+        value: Option<&str>,
+        style: &FontStyle,
+        render: F,
+    ) -> Result<&I>
+    where
+        F: FnOnce() -> Result<I>,
+    {
+        self.clock += 1;
+        // Few labels are alive at once, so a linear search beats hashing the text.
+        let found = self.entries.iter().position(|entry| {
+            entry.text == text
+                && entry.value.as_ref().map(String::as_str) == value
+                && entry.style == *style
+        });
+        let index = match found {
+            Some(index) => index,
+            None => {
+                let image = render()?;
+                if self.entries.len() >= self.capacity {
+                    let oldest = (0..self.entries.len())
+                        .min_by_key(|&index| self.entries[index].last_used)
+                        .unwrap();
+                    self.entries.swap_remove(oldest);
+                }
+                self.entries.push(CachedLabel {
+                    text: text.to_string(),
+                    value: value.map(str::to_string),
+                    style: *style,
+                    image,
+                    last_used: 0,
+                });
+                self.entries.len() - 1
+            }
+        };
+        self.entries[index].last_used = self.clock;
+        Ok(&self.entries[index].image)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quicksilver::graphics::Color;
+
+    fn get(cache: &mut LabelCache<String>, text: &str, rendered: &mut Vec<String>) -> String {
+        let style = FontStyle::new(16.0, Color::BLACK);
+        cache
+            .get_or_render(text, &style, || {
+                rendered.push(text.to_string());
+                Ok(text.to_uppercase())
+            })
+            .unwrap()
+            .clone()
+    }
+
+    #[test]
+    fn renders_each_text_once() {
+        let mut cache = LabelCache::new(4);
+        let mut rendered = Vec::new();
+        assert_eq!(get(&mut cache, "score", &mut rendered), "SCORE");
+        assert_eq!(get(&mut cache, "score", &mut rendered), "SCORE");
+        assert_eq!(rendered, vec!["score"]);
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn evicts_the_least_recently_used() {
+        let mut cache = LabelCache::new(2);
+        let mut rendered = Vec::new();
+        get(&mut cache, "a", &mut rendered);
+        get(&mut cache, "b", &mut rendered);
+        get(&mut cache, "a", &mut rendered);
+        get(&mut cache, "c", &mut rendered);
+        assert_eq!(cache.len(), 2);
+        get(&mut cache, "a", &mut rendered);
+        get(&mut cache, "b", &mut rendered);
+        assert_eq!(rendered, vec!["a", "b", "c", "b"]);
+    }
+
+    #[test]
+    fn styles_are_cached_apart() {
+        let mut cache = LabelCache::new(4);
+        let render = || Ok(());
+        cache
+            .get_or_render("a", &FontStyle::new(16.0, Color::BLACK), render)
+            .unwrap();
+        cache
+            .get_or_render("a", &FontStyle::new(32.0, Color::BLACK), render)
+            .unwrap();
+        assert_eq!(cache.len(), 2);
+        cache
+            .get_or_format("a", "", &FontStyle::new(16.0, Color::BLACK), render)
+            .unwrap();
+        assert_eq!(cache.len(), 3);
+        cache.clear();
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn formats_only_when_the_value_changes() {
+        let mut cache = LabelCache::new(4);
+        let style = FontStyle::new(16.0, Color::BLACK);
+        let mut formatted = 0;
+        for value in &["1", "1", "2", "1"] {
+            let text = cache
+                .get_or_format("hud.score", value, &style, || {
+                    formatted += 1;
+                    Ok(format!("Score {}", value))
+                })
+                .unwrap();
+            assert_eq!(*text, format!("Score {}", value));
+        }
+        assert_eq!(formatted, 2);
+    }
+}
@@ -10,17 +10,39 @@ extern crate log;
 #[cfg(not(target_arch = "wasm32"))]
 extern crate simple_logger;
 
+pub mod animation;
+pub mod background;
+pub mod bounds;
+pub mod camera;
+pub mod collider;
+pub mod collision;
 pub mod component;
+#[cfg(any(debug_assertions, feature = "dev-tools"))]
+pub mod console;
+#[cfg(any(debug_assertions, feature = "dev-tools"))]
+pub mod debug;
 pub mod enemy;
 pub mod entity_factory;
 pub mod game;
 pub mod healing;
 pub mod hero;
 pub mod instant;
+pub mod label;
+pub mod localization;
 pub mod music;
+pub mod particles;
+pub mod physics;
+pub mod power_up;
+pub mod profiler;
 pub mod resources;
 pub mod scene;
+pub mod spatial;
+pub mod sprite;
+pub mod stats;
 pub mod system;
+pub mod tilemap;
+pub mod tween;
+pub mod ui;
 
 #[cfg(test)]
 mod tests {
@@ -0,0 +1,243 @@
+use std::collections::HashMap;
+
+use quicksilver::{
+    combinators::join_all,
+    prelude::{load_file, Error, Future},
+};
+use serde::{Deserialize, Serialize};
+
+pub type StringTable = HashMap<String, String>;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct LocalizationConfig {
+    /// Locale like `pt-BR`, taken from the system or browser when `None`.
+    pub locale: Option<String>,
+    pub default_locale: String,
+    /// Holds a `<locale>.json` string table per locale, an object of keys to strings.
+    pub directory: String,
+    /// Fonts for locales the scene font lacks glyphs for.
+    pub fonts: HashMap<String, String>,
+}
+
+impl Default for LocalizationConfig {
+    fn default() -> LocalizationConfig {
+        LocalizationConfig {
+            locale: None,
+            default_locale: "en".to_string(),
+            directory: "locales".to_string(),
+            fonts: HashMap::new(),
+        }
+    }
+}
+
+impl LocalizationConfig {
+    /// Locales to look strings up in, most specific first: `pt-BR`, `pt`, then the default.
+    pub fn fallback_chain(&self) -> Vec<String> {
+        let mut chain = Vec::new();
+        if let Some(locale) = self.locale.clone().or_else(system_locale) {
+            let language = locale.split('-').next().unwrap_or_default().to_string();
+            chain.push(locale);
+            chain.push(language);
+        }
+        chain.push(self.default_locale.clone());
+        chain.dedup();
+        chain
+    }
+
+    pub fn font(&self, default_font: &str) -> String {
+        self.fallback_chain()
+            .iter()
+            .find_map(|locale| self.fonts.get(locale))
+            .cloned()
+            .unwrap_or_else(|| default_font.to_string())
+    }
+}
+
+/// Turns `pt_BR.UTF-8` into `pt-BR`.
+fn normalize_locale(locale: &str) -> Option<String> {
+    let locale = locale.split('.').next()?.replace('_', "-");
+    if locale.is_empty() || locale == "C" || locale == "POSIX" {
+        None
+    } else {
+        Some(locale)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn system_locale() -> Option<String> {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find_map(|locale| normalize_locale(&locale))
+}
+
+#[cfg(target_arch = "wasm32")]
+fn system_locale() -> Option<String> {
+    use stdweb::unstable::TryInto;
+    let locale: Option<String> = js! {
+        return navigator.language;
+    }
+    .try_into()
+    .ok();
+    locale.and_then(|locale| normalize_locale(&locale))
+}
+
+/// Strings shown on screen, looked up by key. Keys missing from every table are shown as is.
+#[derive(Debug, Clone)]
+pub struct Localization {
+    pub locale: String,
+    /// Most specific first, the built-in strings last.
+    tables: Vec<StringTable>,
+}
+
+impl Default for Localization {
+    fn default() -> Localization {
+        Localization {
+            locale: "en".to_string(),
+            tables: vec![default_strings()],
+        }
+    }
+}
+
+impl Localization {
+    pub fn new(locale: String, mut tables: Vec<StringTable>) -> Localization {
+        tables.push(default_strings());
+        Localization { locale, tables }
+    }
+
+    pub fn get<'a>(&'a self, key: &'a str) -> &'a str {
+        self.tables
+            .iter()
+            .find_map(|table| table.get(key))
+            .map_or(key, String::as_str)
+    }
+
+    /// Looks `key` up, replacing `{name}` placeholders with `args`.
+    pub fn format(&self, key: &str, args: &[(&str, &str)]) -> String {
+        args.iter()
+            .fold(self.get(key).to_string(), |text, (name, value)| {
+                text.replace(&format!("{{{}}}", name), value)
+            })
+    }
+}
+
+/// English strings, used for keys the locale files don't have.
+pub fn default_strings() -> StringTable {
+    [
+        ("menu.start_game", "Start"),
+        ("menu.options", "Options"),
+        ("menu.quit", "Quit"),
+        ("menu.back", "Back"),
+        ("menu.camera_effects", "Camera effects"),
+        ("menu.music_volume", "Music volume"),
+        ("menu.game_speed", "Game speed"),
+        ("menu.speed_slow", "Slow"),
+        ("menu.speed_normal", "Normal"),
+        ("menu.speed_fast", "Fast"),
+        ("menu.retry", "Retry"),
+        ("menu.title", "Title screen"),
+        ("results.victory", "Victory!"),
+        ("results.defeat", "Game over"),
+        ("results.score", "Score: {value}"),
+        ("hud.fps", "{value}"),
+        ("hud.lives", "{value}"),
+        ("hud.score", "{value}"),
+        ("hud.version", "v{value}"),
+        ("hud.effects", "{value}"),
+        ("hud.effect", "{name} {seconds}s"),
+        ("power_up.shield", "Shield"),
+        ("power_up.speed_boost", "Speed"),
+        ("power_up.double_jump", "Jump"),
+        ("power_up.score_multiplier", "Score"),
+    ]
+    .iter()
+    .map(|(key, text)| (key.to_string(), text.to_string()))
+    .collect()
+}
+
+fn load_table(path: String) -> impl Future<Item = StringTable, Error = Error> {
+    load_file(path.clone()).then(move |data| {
+        let table = data.map_err(|error| error.to_string()).and_then(|data| {
+            serde_json::from_slice(data.as_slice()).map_err(|error| error.to_string())
+        });
+        Ok(table.unwrap_or_else(|error| {
+            log::debug!("No string table at {}: {}", path, error);
+            StringTable::new()
+        }))
+    })
+}
+
+/// Loads the tables of the fallback chain, missing ones left empty.
+pub fn load_localization(
+    config: &LocalizationConfig,
+) -> impl Future<Item = Localization, Error = Error> {
+    let chain = config.fallback_chain();
+    let locale = chain[0].clone();
+    log::info!("Using locale {}", locale);
+    let tables = chain
+        .iter()
+        .map(|locale| load_table(format!("{}/{}.json", config.directory, locale)))
+        .collect::<Vec<_>>();
+    join_all(tables).map(move |tables| Localization::new(locale, tables))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chain(locale: &str, default_locale: &str) -> Vec<String> {
+        LocalizationConfig {
+            locale: Some(locale.to_string()),
+            default_locale: default_locale.to_string(),
+            ..LocalizationConfig::default()
+        }
+        .fallback_chain()
+    }
+
+    #[test]
+    fn fallback_chain_goes_from_region_to_language_to_default() {
+        assert_eq!(chain("pt-BR", "en"), vec!["pt-BR", "pt", "en"]);
+        assert_eq!(chain("pt", "en"), vec!["pt", "en"]);
+        assert_eq!(chain("en-US", "en"), vec!["en-US", "en"]);
+        assert_eq!(chain("en", "en"), vec!["en"]);
+    }
+
+    #[test]
+    fn locales_are_normalized() {
+        assert_eq!(normalize_locale("pt_BR.UTF-8"), Some("pt-BR".to_string()));
+        assert_eq!(normalize_locale("C"), None);
+        assert_eq!(normalize_locale(""), None);
+    }
+
+    #[test]
+    fn format_fills_the_placeholders() {
+        let localization = Localization::default();
+        assert_eq!(
+            localization.format("hud.effect", &[("name", "Shield"), ("seconds", "3")]),
+            "Shield 3s"
+        );
+        assert_eq!(
+            localization.format("hud.version", &[("value", "0.1")]),
+            "v0.1"
+        );
+    }
+
+    #[test]
+    fn format_prefers_the_locale_tables() {
+        let table = [("hud.version".to_string(), "versão {value}".to_string())]
+            .iter()
+            .cloned()
+            .collect();
+        let localization = Localization::new("pt".to_string(), vec![table]);
+        assert_eq!(
+            localization.format("hud.version", &[("value", "0.1")]),
+            "versão 0.1"
+        );
+        assert_eq!(localization.get("menu.quit"), "Quit");
+        assert_eq!(
+            localization.format("missing.{value}", &[("value", "1")]),
+            "missing.1"
+        );
+    }
+}
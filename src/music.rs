@@ -35,6 +35,11 @@ impl MusicPlayer {
         Ok(())
     }
 
+    /// Volume from 0.0 to 1.0, used from the next music played.
+    pub fn set_volume(&mut self, volume: f32) {
+        self.volume = volume.max(0.0).min(1.0);
+    }
+
     pub fn play_music(&mut self, music: String) -> Result<()> {
         self.stop_music()?;
         self.current_music = Some(Asset::new(Sound::load(music)));
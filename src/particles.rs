@@ -0,0 +1,360 @@
+use std::{collections::HashMap, time::Duration};
+
+use crate::{
+    component::{Particle, ParticleEmitter, Position, Render, SpriteTransform, Velocity},
+    resources::RenderLayer,
+    sprite::SpriteHandle,
+};
+use quicksilver::geom::Vector;
+use rand::{rngs::ThreadRng, Rng};
+use serde::{Deserialize, Serialize};
+use specs::{Builder, World};
+
+/// Effects collision rules can trigger.
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Hash, Copy, Clone)]
+pub enum ParticleEffectName {
+    Stomp,
+    Hit,
+    Pickup,
+    Explosion,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Copy, Clone)]
+pub enum EmissionMode {
+    /// Emits all particles at once, then the emitter is done.
+    Burst(u32),
+    /// Emits `rate` particles per second, for `duration_ms` or forever when `None`.
+    Continuous { rate: f32, duration_ms: Option<u64> },
+}
+
+/// Values a particle moves between, from its spawn to the end of its life.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone)]
+#[serde(default)]
+pub struct ParticleOverLife {
+    pub start_color: (f32, f32, f32),
+    pub end_color: (f32, f32, f32),
+    pub start_alpha: f32,
+    pub end_alpha: f32,
+    pub start_scale: f32,
+    pub end_scale: f32,
+}
+
+impl Default for ParticleOverLife {
+    fn default() -> ParticleOverLife {
+        ParticleOverLife {
+            start_color: (1.0, 1.0, 1.0),
+            end_color: (1.0, 1.0, 1.0),
+            start_alpha: 1.0,
+            end_alpha: 0.0,
+            start_scale: 1.0,
+            end_scale: 0.5,
+        }
+    }
+}
+
+impl ParticleOverLife {
+    /// Writes the values at `progress`, from 0.0 at spawn to 1.0 at the end of life.
+    pub fn apply(&self, progress: f32, transform: &mut SpriteTransform) {
+        let lerp = |start: f32, end: f32| start + (end - start) * progress;
+        transform.tint = (
+            lerp(self.start_color.0, self.end_color.0),
+            lerp(self.start_color.1, self.end_color.1),
+            lerp(self.start_color.2, self.end_color.2),
+        );
+        transform.alpha = lerp(self.start_alpha, self.end_alpha);
+        let scale = lerp(self.start_scale, self.end_scale);
+        transform.scale = Vector::new(scale, scale);
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ParticleEmitterConfig {
+    pub sprite: String,
+    pub mode: EmissionMode,
+    /// Particles live a random time between the two values.
+    pub lifetime_ms: (u64, u64),
+    /// Each particle gets a random velocity inside this range, per axis.
+    pub velocity_min: Vector,
+    pub velocity_max: Vector,
+    /// Particles spawn at a random point of this area, centered on the emitter.
+    pub spawn_area: Vector,
+    pub gravity: f32,
+    pub over_life: ParticleOverLife,
+    pub layer: RenderLayer,
+}
+
+impl Default for ParticleEmitterConfig {
+    fn default() -> ParticleEmitterConfig {
+        ParticleEmitterConfig {
+            sprite: "fogo".to_string(),
+            mode: EmissionMode::Burst(8),
+            lifetime_ms: (300, 600),
+            velocity_min: Vector::new(-100.0, -100.0),
+            velocity_max: Vector::new(100.0, 100.0),
+            spawn_area: Vector::ZERO,
+            gravity: 0.0,
+            over_life: ParticleOverLife::default(),
+            layer: RenderLayer::Foreground,
+        }
+    }
+}
+
+impl ParticleEmitterConfig {
+    /// Small fading particles left behind by a moving projectile.
+    pub fn trail(sprite: &str) -> ParticleEmitterConfig {
+        ParticleEmitterConfig {
+            sprite: sprite.to_string(),
+            mode: EmissionMode::Continuous {
+                rate: 30.0,
+                duration_ms: None,
+            },
+            lifetime_ms: (150, 300),
+            velocity_min: Vector::new(-15.0, -15.0),
+            velocity_max: Vector::new(15.0, 15.0),
+            over_life: ParticleOverLife {
+                start_alpha: 0.6,
+                start_scale: 0.5,
+                end_scale: 0.1,
+                ..ParticleOverLife::default()
+            },
+            layer: RenderLayer::Projectiles,
+            ..ParticleEmitterConfig::default()
+        }
+    }
+}
+
+/// Emitters placed in the scene when it starts, like falling embers.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct AmbientParticlesConfig {
+    pub position: Vector,
+    pub emitter: ParticleEmitterConfig,
+}
+
+impl Default for AmbientParticlesConfig {
+    fn default() -> AmbientParticlesConfig {
+        AmbientParticlesConfig {
+            position: Vector::ZERO,
+            emitter: ParticleEmitterConfig::default(),
+        }
+    }
+}
+
+pub fn default_particle_effects() -> HashMap<ParticleEffectName, ParticleEmitterConfig> {
+    let mut effects = HashMap::new();
+    effects.insert(
+        ParticleEffectName::Stomp,
+        ParticleEmitterConfig {
+            sprite: "tiro".to_string(),
+            mode: EmissionMode::Burst(10),
+            velocity_min: Vector::new(-150.0, -250.0),
+            velocity_max: Vector::new(150.0, -50.0),
+            gravity: 600.0,
+            ..ParticleEmitterConfig::default()
+        },
+    );
+    effects.insert(
+        ParticleEffectName::Hit,
+        ParticleEmitterConfig {
+            sprite: "fogo".to_string(),
+            mode: EmissionMode::Burst(6),
+            over_life: ParticleOverLife {
+                start_color: (1.0, 0.3, 0.3),
+                start_scale: 0.6,
+                end_scale: 0.2,
+                ..ParticleOverLife::default()
+            },
+            ..ParticleEmitterConfig::default()
+        },
+    );
+    effects.insert(
+        ParticleEffectName::Pickup,
+        ParticleEmitterConfig {
+            sprite: "potion".to_string(),
+            mode: EmissionMode::Burst(6),
+            velocity_min: Vector::new(-60.0, -120.0),
+            velocity_max: Vector::new(60.0, -40.0),
+            over_life: ParticleOverLife {
+                start_scale: 0.4,
+                end_scale: 0.1,
+                ..ParticleOverLife::default()
+            },
+            ..ParticleEmitterConfig::default()
+        },
+    );
+    effects.insert(
+        ParticleEffectName::Explosion,
+        ParticleEmitterConfig {
+            mode: EmissionMode::Burst(16),
+            lifetime_ms: (400, 800),
+            velocity_min: Vector::new(-200.0, -200.0),
+            velocity_max: Vector::new(200.0, 200.0),
+            ..ParticleEmitterConfig::default()
+        },
+    );
+    effects
+}
+
+/// Configured effects and the ones triggered since the last particle update.
+pub struct ParticleEffects {
+    pub effects: HashMap<ParticleEffectName, ParticleEmitterConfig>,
+    pub pending: Vec<(ParticleEffectName, Vector)>,
+}
+
+impl Default for ParticleEffects {
+    fn default() -> ParticleEffects {
+        ParticleEffects::new(default_particle_effects())
+    }
+}
+
+impl ParticleEffects {
+    pub fn new(effects: HashMap<ParticleEffectName, ParticleEmitterConfig>) -> ParticleEffects {
+        ParticleEffects {
+            effects,
+            pending: Vec::new(),
+        }
+    }
+
+    pub fn emit(&mut self, effect: ParticleEffectName, position: Vector) {
+        self.pending.push((effect, position));
+    }
+}
+
+impl ParticleEmitter {
+    pub fn new(config: ParticleEmitterConfig) -> ParticleEmitter {
+        ParticleEmitter {
+            config,
+            elapsed: Duration::from_millis(0),
+            pending: 0.0,
+            despawn: false,
+            sprite: None,
+        }
+    }
+
+    /// Number of particles to emit this tick, or `None` once the emitter is done.
+    pub fn advance(&mut self, time_step: Duration) -> Option<u32> {
+        match self.config.mode {
+            EmissionMode::Burst(count) => {
+                if self.elapsed > Duration::from_millis(0) {
+                    return None;
+                }
+                self.elapsed += time_step;
+                Some(count)
+            }
+            EmissionMode::Continuous { rate, duration_ms } => {
+                if duration_ms.map_or(false, |duration_ms| {
+                    self.elapsed >= Duration::from_millis(duration_ms)
+                }) {
+                    return None;
+                }
+                self.elapsed += time_step;
+                self.pending += rate * time_step.as_micros() as f32 / 1_000_000.0;
+                let count = self.pending.floor();
+                self.pending -= count;
+                Some(count as u32)
+            }
+        }
+    }
+}
+
+fn random_range(rng: &mut ThreadRng, min: f32, max: f32) -> f32 {
+    if max > min {
+        rng.gen_range(min, max)
+    } else {
+        min
+    }
+}
+
+pub fn create_particle<T: Builder>(
+    builder: T,
+    config: &ParticleEmitterConfig,
+    sprite: SpriteHandle,
+    position: Vector,
+    rng: &mut ThreadRng,
+) {
+    let (min_lifetime, max_lifetime) = config.lifetime_ms;
+    let lifetime = random_range(rng, min_lifetime as f32, max_lifetime as f32);
+    let offset = Vector::new(
+        random_range(rng, -config.spawn_area.x, config.spawn_area.x) / 2.0,
+        random_range(rng, -config.spawn_area.y, config.spawn_area.y) / 2.0,
+    );
+    let velocity = Vector::new(
+        random_range(rng, config.velocity_min.x, config.velocity_max.x),
+        random_range(rng, config.velocity_min.y, config.velocity_max.y),
+    );
+    let mut transform = SpriteTransform::default();
+    config.over_life.apply(0.0, &mut transform);
+
+    builder
+        .with(Particle {
+            age: Duration::from_millis(0),
+            lifetime: Duration::from_millis(lifetime as u64),
+            gravity: config.gravity,
+            over_life: config.over_life,
+        })
+        .with(Position {
+            position: position + offset,
+        })
+        .with(Velocity { velocity })
+        .with(Render {
+            sprite,
+            layer: config.layer,
+            z: 0.0,
+        })
+        .with(transform)
+        .build();
+}
+
+pub fn create_ambient_particles(world: &mut World, configs: &[AmbientParticlesConfig]) {
+    for config in configs {
+        world
+            .create_entity()
+            .with(Position {
+                position: config.position,
+            })
+            .with(ParticleEmitter::new(config.emitter.clone()))
+            .build();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn emitter(mode: EmissionMode) -> ParticleEmitter {
+        ParticleEmitter::new(ParticleEmitterConfig {
+            mode,
+            ..ParticleEmitterConfig::default()
+        })
+    }
+
+    #[test]
+    fn burst_emits_once() {
+        let mut emitter = emitter(EmissionMode::Burst(8));
+        assert_eq!(emitter.advance(Duration::from_millis(16)), Some(8));
+        assert_eq!(emitter.advance(Duration::from_millis(16)), None);
+    }
+
+    #[test]
+    fn continuous_carries_fractions_over() {
+        let mut emitter = emitter(EmissionMode::Continuous {
+            rate: 10.0,
+            duration_ms: None,
+        });
+        let counts = (0..4)
+            .map(|_| emitter.advance(Duration::from_millis(50)))
+            .collect::<Vec<_>>();
+        assert_eq!(counts, vec![Some(0), Some(1), Some(0), Some(1)]);
+    }
+
+    #[test]
+    fn continuous_stops_after_its_duration() {
+        let mut emitter = emitter(EmissionMode::Continuous {
+            rate: 20.0,
+            duration_ms: Some(100),
+        });
+        assert_eq!(emitter.advance(Duration::from_millis(100)), Some(2));
+        assert_eq!(emitter.advance(Duration::from_millis(100)), None);
+    }
+}
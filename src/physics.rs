@@ -0,0 +1,49 @@
+use crate::{bounds::WorldBounds, tilemap::TileCollisionMap};
+use serde::{Deserialize, Serialize};
+use specs::World;
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone)]
+#[serde(default)]
+pub struct PhysicsConfig {
+    pub gravity: f32,
+    pub terminal_velocity: f32,
+    pub jump_impulse: f32,
+    pub jump_cut: f32,
+    /// Flat floor for scenes without a tile map, `None` for no floor. Maps with a collision
+    /// layer use their solid tiles instead.
+    pub ground_height: Option<f32>,
+}
+
+impl Default for PhysicsConfig {
+    fn default() -> PhysicsConfig {
+        PhysicsConfig {
+            gravity: 1200.0,
+            terminal_velocity: 900.0,
+            jump_impulse: 550.0,
+            jump_cut: 0.5,
+            ground_height: Some(460.0),
+        }
+    }
+}
+
+impl PhysicsConfig {
+    /// Height of the flat floor, which only exists while no collision tiles are loaded.
+    pub fn floor(&self, tile_map: &TileCollisionMap) -> Option<f32> {
+        if tile_map.is_empty() {
+            self.ground_height
+        } else {
+            None
+        }
+    }
+}
+
+/// Height of the ground under `x`: the flat floor, else the highest solid tile of that column,
+/// else the bottom of the world bounds.
+pub fn ground_at(world: &World, x: f32) -> f32 {
+    let tile_map = world.read_resource::<TileCollisionMap>();
+    world
+        .read_resource::<PhysicsConfig>()
+        .floor(&tile_map)
+        .or_else(|| tile_map.surface(x))
+        .unwrap_or_else(|| world.read_resource::<WorldBounds>().bottom())
+}
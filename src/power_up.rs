@@ -0,0 +1,156 @@
+use crate::{
+    bounds::{DespawnPolicy, WorldBounds},
+    camera::Camera,
+    collider::Hitbox,
+    collision::{CollisionLayerConfig, CollisionLayerName},
+    component::{
+        Collider, CollisionLayer, Lifetime, OutOfBounds, Position, PowerUp, Render, RigidBody,
+        SpriteTransform, Tween, Velocity,
+    },
+    resources::RenderLayer,
+    sprite::intern_sprite,
+    tween::TweenConfig,
+};
+use quicksilver::geom::Vector;
+use serde::{Deserialize, Serialize};
+use specs::{Builder, Entity, World};
+use std::time::Duration;
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Hash, Copy, Clone)]
+pub enum PowerUpKind {
+    Shield,
+    SpeedBoost,
+    DoubleJump,
+    ScoreMultiplier,
+}
+
+impl PowerUpKind {
+    /// Key of the name shown in the HUD.
+    pub fn string_key(self) -> &'static str {
+        match self {
+            PowerUpKind::Shield => "power_up.shield",
+            PowerUpKind::SpeedBoost => "power_up.speed_boost",
+            PowerUpKind::DoubleJump => "power_up.double_jump",
+            PowerUpKind::ScoreMultiplier => "power_up.score_multiplier",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct PowerUpConfig {
+    pub kind: PowerUpKind,
+    pub sprite: String,
+    pub velocity: Vector,
+    pub duration_ms: u64,
+    pub magnitude: f32,
+    pub score: i32,
+    pub gravity_scale: Option<f32>,
+    pub collider: Vec<Hitbox>,
+    pub collision_layer: CollisionLayerConfig,
+    pub despawn: DespawnPolicy,
+    pub lifetime_ms: Option<u64>,
+    pub tween: Option<TweenConfig>,
+    pub layer: RenderLayer,
+    /// Order inside the layer, higher is drawn later.
+    pub z: f32,
+}
+
+impl Default for PowerUpConfig {
+    fn default() -> PowerUpConfig {
+        PowerUpConfig {
+            kind: PowerUpKind::Shield,
+            sprite: "escudo".to_string(),
+            velocity: Vector::new(0.0, 250.0),
+            duration_ms: 8000,
+            magnitude: 1.0,
+            score: 50,
+            gravity_scale: Some(1.0),
+            collider: crate::collider::body(Vector::new(32.0, 32.0)),
+            collision_layer: CollisionLayerConfig::new(
+                &[CollisionLayerName::Pickup],
+                &[CollisionLayerName::Hero],
+            ),
+            despawn: DespawnPolicy::Offscreen,
+            lifetime_ms: Some(10000),
+            tween: Some(TweenConfig::bob(6.0, 600)),
+            layer: RenderLayer::World,
+            z: 0.0,
+        }
+    }
+}
+
+pub fn default_power_ups() -> Vec<PowerUpConfig> {
+    vec![
+        PowerUpConfig::default(),
+        PowerUpConfig {
+            kind: PowerUpKind::SpeedBoost,
+            sprite: "velocidade".to_string(),
+            duration_ms: 6000,
+            magnitude: 1.5,
+            ..PowerUpConfig::default()
+        },
+        PowerUpConfig {
+            kind: PowerUpKind::DoubleJump,
+            sprite: "pulo_duplo".to_string(),
+            duration_ms: 10000,
+            magnitude: 1.0,
+            ..PowerUpConfig::default()
+        },
+        PowerUpConfig {
+            kind: PowerUpKind::ScoreMultiplier,
+            sprite: "multiplicador".to_string(),
+            duration_ms: 10000,
+            magnitude: 2.0,
+            ..PowerUpConfig::default()
+        },
+    ]
+}
+
+pub fn create_power_up(world: &mut World, config: PowerUpConfig) -> Entity {
+    let sprite = intern_sprite(world, &config.sprite);
+    let bounds = *world.read_resource::<WorldBounds>();
+    let view = world.read_resource::<Camera>().view_area();
+    let position = bounds.drop_position(&view);
+    let mut builder = world
+        .create_entity()
+        .with(OutOfBounds {
+            policy: config.despawn,
+        })
+        .with(Position { position })
+        .with(Velocity {
+            velocity: config.velocity,
+        })
+        .with(Render {
+            sprite,
+            layer: config.layer,
+            z: config.z,
+        })
+        .with(Collider {
+            hitboxes: config.collider,
+        })
+        .with(CollisionLayer::new(&config.collision_layer))
+        .with(PowerUp {
+            kind: config.kind,
+            duration: Duration::from_millis(config.duration_ms),
+            magnitude: config.magnitude,
+            score: config.score,
+        });
+    if let Some(gravity_scale) = config.gravity_scale {
+        builder = builder.with(RigidBody {
+            gravity_scale,
+            grounded: false,
+        });
+    }
+    if let Some(lifetime_ms) = config.lifetime_ms {
+        builder = builder.with(Lifetime {
+            remaining: Duration::from_millis(lifetime_ms),
+        });
+    }
+    if let Some(tween) = config.tween {
+        builder = builder
+            .with(Tween::new(tween))
+            .with(SpriteTransform::default());
+    }
+    builder.build()
+}
@@ -0,0 +1,308 @@
+use std::{collections::VecDeque, time::Duration};
+
+use quicksilver::{
+    geom::{Line, Rectangle, Shape, Vector},
+    graphics::{Background::Col, Background::Img, Color, Font, FontStyle},
+    lifecycle::Window,
+    Result,
+};
+use serde::Serialize;
+
+use crate::label::LabelCache;
+
+/// Frames kept for the rolling averages and the frame graph.
+const HISTORY: usize = 120;
+/// Trace events kept while capturing, about a minute of play.
+const MAX_TRACE_EVENTS: usize = 100_000;
+const FRAME_BUDGET_MS: f32 = 16.7;
+/// Frames between updates of the overlay numbers, so their labels are rendered only then.
+const OVERLAY_REFRESH_FRAMES: u64 = 30;
+
+fn millis(duration: Duration) -> f32 {
+    duration.as_micros() as f32 / 1000.0
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Timings {
+    samples: VecDeque<Duration>,
+}
+
+impl Timings {
+    fn push(&mut self, duration: Duration) {
+        if self.samples.len() == HISTORY {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(duration);
+    }
+
+    pub fn last(&self) -> Duration {
+        self.samples.back().cloned().unwrap_or_default()
+    }
+
+    pub fn average(&self) -> Duration {
+        if self.samples.is_empty() {
+            return Duration::default();
+        }
+        self.samples.iter().sum::<Duration>() / self.samples.len() as u32
+    }
+
+    pub fn max(&self) -> Duration {
+        self.samples.iter().max().cloned().unwrap_or_default()
+    }
+}
+
+/// Complete event of the Chrome trace format, times in microseconds.
+#[derive(Serialize, Debug, Clone)]
+struct TraceEvent {
+    name: &'static str,
+    ph: &'static str,
+    ts: u64,
+    dur: u64,
+    pid: u32,
+    tid: u32,
+}
+
+#[derive(Serialize)]
+struct Trace<'a> {
+    #[serde(rename = "traceEvents")]
+    trace_events: &'a [TraceEvent],
+}
+
+/// Time taken by each system, over the last frames.
+#[derive(Debug, Default)]
+pub struct Profiler {
+    /// In the order they first ran.
+    pub systems: Vec<(&'static str, Timings)>,
+    pub frames: Timings,
+    pub entity_count: usize,
+    /// Draws the timings over the scene.
+    pub overlay: bool,
+    trace: Option<Vec<TraceEvent>>,
+    /// Time taken by each system this frame, summed over the fixed steps run.
+    frame: Vec<(&'static str, Duration)>,
+    frame_count: u64,
+    /// Frame summary then the average/max of each system, as last shown on the overlay.
+    overlay_values: Vec<String>,
+}
+
+impl Profiler {
+    /// `start` is measured from the scene creation, for the trace. The timings get the sum
+    /// of the runs of a frame once it ends.
+    pub fn record(&mut self, name: &'static str, start: Duration, duration: Duration) {
+        match self.frame.iter_mut().find(|(other, _)| *other == name) {
+            Some((_, total)) => *total += duration,
+            None => self.frame.push((name, duration)),
+        }
+        if let Some(trace) = self.trace.as_mut() {
+            if trace.len() < MAX_TRACE_EVENTS {
+                trace.push(TraceEvent {
+                    name,
+                    ph: "X",
+                    ts: start.as_micros() as u64,
+                    dur: duration.as_micros() as u64,
+                    pid: 1,
+                    tid: 1,
+                });
+            }
+        }
+    }
+
+    /// Systems that didn't run this frame, e.g. while the time scale skips steps, take 0.
+    pub fn end_frame(&mut self, frame_time: Duration, entity_count: usize) {
+        for (name, timings) in self.systems.iter_mut() {
+            let total = self
+                .frame
+                .iter()
+                .find(|(other, _)| *other == *name)
+                .map_or(Duration::default(), |(_, total)| *total);
+            timings.push(total);
+        }
+        for (name, total) in self.frame.drain(..) {
+            if self.systems.iter().all(|(other, _)| *other != name) {
+                let mut timings = Timings::default();
+                timings.push(total);
+                self.systems.push((name, timings));
+            }
+        }
+        self.frames.push(frame_time);
+        self.entity_count = entity_count;
+        if self.overlay
+            && (self.frame_count % OVERLAY_REFRESH_FRAMES == 0
+                || self.overlay_values.len() != self.systems.len() + 1)
+        {
+            self.refresh_overlay_values();
+        }
+        self.frame_count += 1;
+    }
+
+    fn refresh_overlay_values(&mut self) {
+        self.overlay_values.clear();
+        self.overlay_values.push(format!(
+            "Frame {:.1} ms, max {:.1} ms, {} entities",
+            millis(self.frames.average()),
+            millis(self.frames.max()),
+            self.entity_count
+        ));
+        for (_, timings) in &self.systems {
+            self.overlay_values.push(format!(
+                "{:.2}/{:.2}",
+                millis(timings.average()),
+                millis(timings.max())
+            ));
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Timings> {
+        self.systems
+            .iter()
+            .find(|(other, _)| *other == name)
+            .map(|(_, timings)| timings)
+    }
+
+    pub fn start_trace(&mut self) {
+        self.trace = Some(Vec::new());
+    }
+
+    /// Stops capturing, returning the events as Chrome trace JSON, for `chrome://tracing`.
+    pub fn stop_trace(&mut self) -> Option<String> {
+        self.trace.take().map(|trace_events| {
+            serde_json::to_string(&Trace {
+                trace_events: &trace_events,
+            })
+            .unwrap()
+        })
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn export_chrome_trace(json: &str, path: &str) {
+    match std::fs::write(path, json) {
+        Ok(()) => log::info!("Trace written to {}", path),
+        Err(error) => log::warn!("Couldn't write the trace to {}: {}", path, error),
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn export_chrome_trace(_json: &str, _path: &str) {
+    log::warn!("Traces can only be exported from native builds");
+}
+
+/// Frame time graph and average/max bars for each system, in screen space. The numbers are
+/// only updated every few frames, so all the text comes from the label cache in between.
+pub fn draw_profiler(
+    profiler: &Profiler,
+    window: &mut Window,
+    font: &mut Font,
+    cache: &mut LabelCache,
+) -> Result<()> {
+    let style = FontStyle::new(14.0, Color::BLACK);
+    let (left, top, row) = (10.0, 60.0, 16.0);
+    let rows = profiler.systems.len() + 2;
+    window.draw(
+        &Rectangle::new((left - 5.0, top - 5.0), (330.0, rows as f32 * row + 70.0)),
+        Col(Color::WHITE.with_alpha(0.8)),
+    );
+
+    let mut text_at = |text: &str, position: Vector| -> Result<()> {
+        let image = cache.get_or_render(text, &style, || font.render(text, &style))?;
+        window.draw(&image.area().translate(position), Img(image));
+        Ok(())
+    };
+    let mut values = profiler.overlay_values.iter();
+    if let Some(summary) = values.next() {
+        text_at(summary, Vector::new(left, top))?;
+    }
+    for (index, (name, _)) in profiler.systems.iter().enumerate() {
+        let y = top + (index + 1) as f32 * row;
+        text_at(name, Vector::new(left, y))?;
+        if let Some(value) = values.next() {
+            text_at(value, Vector::new(left + 155.0, y))?;
+        }
+    }
+
+    // Bars are 20 px per millisecond, the tick is the max.
+    let bars_left = left + 220.0;
+    for (index, (_, timings)) in profiler.systems.iter().enumerate() {
+        let y = top + (index + 1) as f32 * row + 3.0;
+        let average = (millis(timings.average()) * 20.0).min(100.0);
+        let max = (millis(timings.max()) * 20.0).min(100.0);
+        window.draw(
+            &Rectangle::new((bars_left, y), (average.max(1.0), row - 6.0)),
+            Col(Color::BLUE),
+        );
+        window.draw(
+            &Rectangle::new((bars_left + max, y), (1.0, row - 6.0)),
+            Col(Color::RED),
+        );
+    }
+
+    // Frame graph, 2 px per millisecond with a line at the frame budget.
+    let bottom = top + rows as f32 * row + 60.0;
+    for (index, frame) in profiler.frames.samples.iter().enumerate() {
+        let ms = millis(*frame);
+        let height = (ms * 2.0).min(60.0);
+        let color = if ms > FRAME_BUDGET_MS {
+            Color::RED
+        } else {
+            Color::GREEN
+        };
+        window.draw(
+            &Rectangle::new((left + index as f32 * 2.5, bottom - height), (2.0, height)),
+            Col(color),
+        );
+    }
+    let budget = bottom - FRAME_BUDGET_MS * 2.0;
+    window.draw(
+        &Line::new((left, budget), (left + HISTORY as f32 * 2.5, budget)),
+        Col(Color::BLACK),
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn steps_of_a_frame_are_summed() {
+        let mut profiler = Profiler::default();
+        profiler.record("PhysicsSystem", ms(0), ms(2));
+        profiler.record("PhysicsSystem", ms(2), ms(3));
+        assert!(profiler.get("PhysicsSystem").is_none());
+        profiler.end_frame(ms(16), 0);
+        assert_eq!(profiler.get("PhysicsSystem").unwrap().last(), ms(5));
+    }
+
+    #[test]
+    fn overlay_values_refresh_every_few_frames() {
+        let mut profiler = Profiler {
+            overlay: true,
+            ..Profiler::default()
+        };
+        profiler.record("PhysicsSystem", ms(0), ms(4));
+        profiler.end_frame(ms(16), 0);
+        assert_eq!(profiler.overlay_values[1], "4.00/4.00");
+        for _ in 1..OVERLAY_REFRESH_FRAMES {
+            profiler.end_frame(ms(16), 0);
+        }
+        assert_eq!(profiler.overlay_values[1], "4.00/4.00");
+        profiler.end_frame(ms(16), 0);
+        assert_ne!(profiler.overlay_values[1], "4.00/4.00");
+    }
+
+    #[test]
+    fn skipped_systems_take_zero() {
+        let mut profiler = Profiler::default();
+        profiler.record("PhysicsSystem", ms(0), ms(4));
+        profiler.end_frame(ms(16), 0);
+        profiler.end_frame(ms(16), 0);
+        let timings = profiler.get("PhysicsSystem").unwrap();
+        assert_eq!(timings.last(), ms(0));
+        assert_eq!(timings.average(), ms(2));
+        assert_eq!(timings.max(), ms(4));
+    }
+}
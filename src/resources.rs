@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 
+use serde::{Deserialize, Serialize};
 use specs::BitSet;
 
 #[derive(Debug, Eq, PartialEq, Hash, Copy, Clone)]
@@ -8,6 +9,65 @@ pub enum LabelVariable {
     FramesPerSecond,
     Score,
     EngineVersion,
+    ActiveEffects,
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Hash, Copy, Clone)]
+pub enum RenderLayer {
+    Background,
+    World,
+    Projectiles,
+    Foreground,
+    /// Drawn in screen space, over the world.
+    Hud,
+}
+
+impl Default for RenderLayer {
+    fn default() -> RenderLayer {
+        RenderLayer::World
+    }
+}
+
+/// Draw order of the render layers, back to front. Layers left out are not drawn.
+pub struct RenderLayers {
+    pub order: Vec<RenderLayer>,
+    /// Layers whose sprites at the same depth are grouped by sprite, so draws sharing a texture
+    /// are batched. Overlapping sprites there may swap order.
+    pub batched: Vec<RenderLayer>,
+}
+
+impl Default for RenderLayers {
+    fn default() -> RenderLayers {
+        RenderLayers {
+            order: default_render_layers(),
+            batched: default_batched_layers(),
+        }
+    }
+}
+
+impl RenderLayers {
+    pub fn rank(&self, layer: RenderLayer) -> Option<usize> {
+        self.order.iter().position(|other| *other == layer)
+    }
+
+    pub fn is_batched(&self, layer: RenderLayer) -> bool {
+        self.batched.contains(&layer)
+    }
+}
+
+pub fn default_render_layers() -> Vec<RenderLayer> {
+    vec![
+        RenderLayer::Background,
+        RenderLayer::World,
+        RenderLayer::Projectiles,
+        RenderLayer::Foreground,
+        RenderLayer::Hud,
+    ]
+}
+
+/// Tiles and projectiles are many copies of a few sprites that rarely overlap.
+pub fn default_batched_layers() -> Vec<RenderLayer> {
+    vec![RenderLayer::Background, RenderLayer::Projectiles]
 }
 
 #[derive(Debug, Eq, PartialEq, Hash, Copy, Clone)]
@@ -36,3 +96,82 @@ pub enum KeyboardKeys {
 pub struct PressedKeys {
     pub pressed_keys: BitSet,
 }
+
+/// Speed of the game updates, 1.0 being one fixed step per frame.
+pub struct TimeScale {
+    pub scale: f32,
+    accumulator: f32,
+}
+
+impl Default for TimeScale {
+    fn default() -> TimeScale {
+        TimeScale {
+            scale: 1.0,
+            accumulator: 0.0,
+        }
+    }
+}
+
+impl TimeScale {
+    pub fn new(scale: f32) -> TimeScale {
+        TimeScale {
+            scale,
+            ..TimeScale::default()
+        }
+    }
+
+    /// Fixed steps to run this frame.
+    pub fn steps(&mut self) -> u32 {
+        self.accumulator += self.scale;
+        let steps = self.accumulator.floor();
+        self.accumulator -= steps;
+        steps as u32
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum SceneCommand {
+    SkipToBoss,
+    ReloadConfig,
+}
+
+/// Requests for the scene itself, which systems and console commands can't reach.
+#[derive(Default)]
+pub struct SceneCommands {
+    pub pending: Vec<SceneCommand>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn steps(scale: f32, frames: usize) -> Vec<u32> {
+        let mut time_scale = TimeScale {
+            scale,
+            ..TimeScale::default()
+        };
+        (0..frames).map(|_| time_scale.steps()).collect()
+    }
+
+    #[test]
+    fn normal_speed_runs_one_step_per_frame() {
+        assert_eq!(steps(1.0, 3), vec![1, 1, 1]);
+    }
+
+    #[test]
+    fn slow_motion_skips_frames() {
+        assert_eq!(steps(0.5, 4), vec![0, 1, 0, 1]);
+        assert_eq!(steps(0.25, 8).iter().sum::<u32>(), 2);
+    }
+
+    #[test]
+    fn fast_forward_runs_several_steps() {
+        assert_eq!(steps(2.0, 2), vec![2, 2]);
+        assert_eq!(steps(1.5, 2), vec![1, 2]);
+    }
+
+    #[test]
+    fn paused_runs_nothing() {
+        assert_eq!(steps(0.0, 3), vec![0, 0, 0]);
+    }
+}
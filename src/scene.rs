@@ -1,28 +1,60 @@
 use serde::{Deserialize, Serialize};
-use std::{cell::RefCell, rc::Rc};
+use std::{cell::RefCell, collections::HashMap, rc::Rc, time::Duration};
 
 use crate::{
+    background::{create_background, BackgroundLayerConfig},
+    bounds::WorldBounds,
+    camera::{Camera, CameraConfig, CameraEffects, CameraEffectsConfig},
+    collision::{default_collision_rules, CollisionRule, CollisionRules},
     component::{
-        Background, Boss, CalculateOutOfBounds, ChangeSprite, Enemy, Fireball, Healing, Hero,
-        Label, Position, Render, Shooter, Velocity,
+        ActiveEffects, Animation, Background, Boss, ChangeSprite, Collider, CollisionLayer,
+        ContinuousCollision, Dying, Enemy, Fireball, Healing, Hero, Hurt, Label, Lifetime, MapTile,
+        OutOfBounds, Particle, ParticleEmitter, Position, PowerUp, Render, RigidBody, Shooter,
+        SpriteTransform, Tween, Velocity,
     },
     enemy::BossConfig,
-    entity_factory::{EntityFactory, EntityFactoryConfig},
+    entity_factory::{EntityFactory, EntityFactoryConfig, PrefabsConfig},
     hero::HeroConfig,
+    instant::Instant,
+    label::LabelCache,
+    localization::{load_localization, Localization, LocalizationConfig},
     music::MusicPlayer,
+    particles::{
+        create_ambient_particles, default_particle_effects, AmbientParticlesConfig,
+        ParticleEffectName, ParticleEffects, ParticleEmitterConfig,
+    },
+    physics::PhysicsConfig,
+    profiler::{draw_profiler, Profiler},
     resources::{
-        GameStateFlag, GameStateFlagRes, KeyboardKeys, LabelVariable, PressedKeys,
-        VariableDictionary,
+        default_batched_layers, default_render_layers, GameStateFlag, GameStateFlagRes,
+        KeyboardKeys, LabelVariable, PressedKeys, RenderLayer, RenderLayers, SceneCommand,
+        SceneCommands, TimeScale, VariableDictionary,
     },
+    spatial::SpatialGrid,
+    sprite::{SpriteImages, SpriteTable},
+    stats::{export_session, Outcome, SessionStats},
+    system::resolve_images,
     system::{
-        CollisionSystem, FireballSystem, HeroBlinkingSystem, HeroControlSystem, LabelRenderSystem,
-        OutOfBoundsSystem, RenderSystem, WalkSystem,
+        ActiveEffectsSystem, AnimationStateSystem, AnimationSystem, BackgroundRenderSystem,
+        BackgroundScrollSystem, CameraEffectsSystem, CameraSystem, CollisionSystem, DyingSystem,
+        FacingSystem, FireballSystem, HeroBlinkingSystem, HeroControlSystem, HurtSystem,
+        LifetimeSystem, OutOfBoundsSystem, ParticleSystem, PhysicsSystem, RenderPass, RenderSystem,
+        SpatialGridSystem, TweenSystem, WalkSystem,
     },
+    tilemap::{TileCollisionMap, TileMapConfig, TiledMap},
+    tween::TweenEvents,
+    ui::{Ui, UiAction, UiConfig, UiEvent, UiValue, GAME_SPEEDS},
 };
 
+#[cfg(any(debug_assertions, feature = "dev-tools"))]
+use crate::{console::Console, debug::DebugOverlay, profiler::export_chrome_trace};
+
 use quicksilver::{graphics::Atlas, prelude::*};
 
-use specs::prelude::*;
+use specs::{prelude::*, Resources};
+
+/// Scene config loaded on startup and by the console `reload` command.
+pub const SCENE_CONFIG_PATH: &str = "scene.json";
 
 #[derive(PartialEq, Copy, Clone)]
 enum GameState {
@@ -38,18 +70,47 @@ enum GameState {
 pub struct SceneConfig {
     pub atlas: String,
     pub font: String,
-    pub main_background: String,
-    pub defeat_background: String,
-    pub victory_background: String,
+    pub localization: LocalizationConfig,
+    #[serde(deserialize_with = "crate::background::deserialize_layers")]
+    pub main_background: Vec<BackgroundLayerConfig>,
+    #[serde(deserialize_with = "crate::background::deserialize_layers")]
+    pub defeat_background: Vec<BackgroundLayerConfig>,
+    #[serde(deserialize_with = "crate::background::deserialize_layers")]
+    pub victory_background: Vec<BackgroundLayerConfig>,
     pub hero_config: HeroConfig,
     pub boss_config: BossConfig,
     pub entity_factory_config: EntityFactoryConfig,
+    pub prefabs: PrefabsConfig,
+    pub physics_config: PhysicsConfig,
+    pub world_bounds: WorldBounds,
+    pub camera_config: CameraConfig,
+    pub camera_effects: CameraEffectsConfig,
+    pub collision_rules: Vec<CollisionRule>,
+    /// Draw order, back to front. Layers left out are not drawn.
+    pub render_layers: Vec<RenderLayer>,
+    /// Layers drawn grouped by sprite, see `RenderLayers::batched`.
+    pub batched_layers: Vec<RenderLayer>,
+    /// Rendered label images kept around for reuse.
+    pub label_cache_size: usize,
+    pub particle_effects: HashMap<ParticleEffectName, ParticleEmitterConfig>,
+    pub ambient_particles: Vec<AmbientParticlesConfig>,
+    pub map: Option<TileMapConfig>,
     pub boss_cycle: u32,
     pub new_body_cycle: u64,
+    /// Where the session stats are written at game over, on native builds.
+    pub stats_dir: Option<String>,
+    /// Where the Chrome trace captured with F5 is written, on native builds.
+    pub trace_path: Option<String>,
     pub normal_music: String,
     pub boss_music: String,
     pub game_over_music: String,
     pub victory_music: String,
+    /// From 0.0 to 1.0.
+    pub music_volume: f32,
+    /// Time scale the runs start with, set from the options menu.
+    pub game_speed: f32,
+    /// Menu screens.
+    pub ui: UiConfig,
 }
 
 impl Default for SceneConfig {
@@ -57,18 +118,36 @@ impl Default for SceneConfig {
         SceneConfig {
             atlas: "evil_alligator.atlas".to_string(),
             font: "cmunrm.ttf".to_string(),
-            main_background: "cenario".to_string(),
-            defeat_background: "inferno".to_string(),
-            victory_background: "ceu".to_string(),
+            localization: LocalizationConfig::default(),
+            main_background: vec![BackgroundLayerConfig::fixed("cenario")],
+            defeat_background: vec![BackgroundLayerConfig::fixed("inferno")],
+            victory_background: vec![BackgroundLayerConfig::fixed("ceu")],
             hero_config: HeroConfig::default(),
             boss_config: BossConfig::default(),
             entity_factory_config: EntityFactoryConfig::default(),
+            prefabs: PrefabsConfig::default(),
+            physics_config: PhysicsConfig::default(),
+            world_bounds: WorldBounds::default(),
+            camera_config: CameraConfig::default(),
+            camera_effects: CameraEffectsConfig::default(),
+            collision_rules: default_collision_rules(),
+            render_layers: default_render_layers(),
+            batched_layers: default_batched_layers(),
+            label_cache_size: 32,
+            particle_effects: default_particle_effects(),
+            ambient_particles: Vec::new(),
+            map: None,
             boss_cycle: 11,
             new_body_cycle: 210,
+            stats_dir: Some("stats".to_string()),
+            trace_path: Some("trace.json".to_string()),
             normal_music: "music/normal.ogg".to_string(),
             boss_music: "music/boss.ogg".to_string(),
             game_over_music: "music/gameover.ogg".to_string(),
             victory_music: "music/victory.ogg".to_string(),
+            music_volume: 0.75,
+            game_speed: 1.0,
+            ui: UiConfig::default(),
         }
     }
 }
@@ -76,7 +155,18 @@ impl Default for SceneConfig {
 pub struct Scene {
     world: World,
     atlas: Rc<RefCell<Asset<Atlas>>>,
+    sprite_images: Rc<RefCell<SpriteImages>>,
+    label_cache: Rc<RefCell<LabelCache>>,
+    ui: Ui,
+    #[cfg(any(debug_assertions, feature = "dev-tools"))]
+    debug_overlay: DebugOverlay,
+    #[cfg(any(debug_assertions, feature = "dev-tools"))]
+    console: Console,
     font: Rc<RefCell<Asset<Font>>>,
+    /// Until they're loaded and added to the world.
+    strings: Option<Asset<Localization>>,
+    map: Option<Asset<Option<TiledMap>>>,
+    boss_position: Option<Vector>,
     hero: Entity,
     state: GameState,
     cycle_timer: u64,
@@ -84,49 +174,61 @@ pub struct Scene {
     music_player: MusicPlayer,
     entity_factory: EntityFactory,
     config: SceneConfig,
+    reloading: Option<Asset<Option<SceneConfig>>>,
+    profiler_origin: Instant,
+    profiler_cache: RefCell<LabelCache>,
+    last_frame: Option<Instant>,
+    sessions: Vec<SessionStats>,
 }
 
 impl Scene {
     pub fn new(config: SceneConfig) -> Result<Self> {
+        config.world_bounds.check();
         let atlas = Rc::new(RefCell::new(Asset::new(Atlas::load(config.atlas.clone()))));
-        let font = Rc::new(RefCell::new(Asset::new(Font::load(config.font.clone()))));
-        let music_player = MusicPlayer::new()?;
-
-        let mut world = World::new();
-        register_components(&mut world);
-        add_resorces(&mut world);
-
-        create_background(&mut world, config.main_background.clone());
-        create_label(
-            &mut world,
-            LabelVariable::FramesPerSecond,
-            FontStyle::new(48.0, Color::BLACK),
-            Vector::new(20, 587),
-        );
-        create_label(
-            &mut world,
-            LabelVariable::HeroLives,
-            FontStyle::new(48.0, Color::BLACK),
-            Vector::new(10, 20),
-        );
-        create_label(
-            &mut world,
-            LabelVariable::Score,
-            FontStyle::new(48.0, Color::BLACK),
-            Vector::new(730, 20),
+        let font = Rc::new(RefCell::new(Asset::new(Font::load(
+            config.localization.font(&config.font),
+        ))));
+        let strings = Asset::new(load_localization(&config.localization));
+        let map = config
+            .map
+            .as_ref()
+            .map(|map| Asset::new(load_map(map.path.clone())));
+        let mut music_player = MusicPlayer::new()?;
+        music_player.set_volume(config.music_volume);
+
+        let (world, hero) = create_run_world(&config);
+
+        let mut ui = Ui::new(config.ui.clone());
+        ui.set_value(
+            "camera_effects",
+            UiValue::Bool(config.camera_effects.enabled),
         );
-        create_label(
-            &mut world,
-            LabelVariable::EngineVersion,
-            FontStyle::new(48.0, Color::BLACK),
-            Vector::new(730, 587),
+        ui.set_value("music_volume", UiValue::Number(config.music_volume));
+        if let Some(index) = GAME_SPEEDS
+            .iter()
+            .position(|(_, speed)| *speed == config.game_speed)
+        {
+            ui.set_value("game_speed", UiValue::Index(index));
+        }
+        ui.open(
+            &config.ui.title_screen,
+            &mut world.write_resource::<SpriteTable>(),
         );
-        let hero = crate::hero::create_hero(&mut world, config.hero_config.clone());
 
         Ok(Scene {
             world,
             atlas,
+            sprite_images: Rc::new(RefCell::new(SpriteImages::default())),
+            label_cache: Rc::new(RefCell::new(LabelCache::new(config.label_cache_size))),
+            ui,
+            #[cfg(any(debug_assertions, feature = "dev-tools"))]
+            debug_overlay: DebugOverlay::default(),
+            #[cfg(any(debug_assertions, feature = "dev-tools"))]
+            console: Console::default(),
             font,
+            strings: Some(strings),
+            map,
+            boss_position: None,
             hero,
             state: GameState::WaitingInput,
             cycle_timer: 0,
@@ -134,24 +236,58 @@ impl Scene {
             music_player,
             entity_factory: EntityFactory::new(config.entity_factory_config.clone())?,
             config,
+            reloading: None,
+            profiler_origin: Instant::now(),
+            profiler_cache: RefCell::new(LabelCache::new(128)),
+            last_frame: None,
+            sessions: Vec::new(),
         })
     }
 
+    /// Stats of the runs played so far.
+    pub fn sessions(&self) -> &[SessionStats] {
+        &self.sessions
+    }
+
+    /// For registering extra console commands.
+    #[cfg(any(debug_assertions, feature = "dev-tools"))]
+    pub fn console_mut(&mut self) -> &mut Console {
+        &mut self.console
+    }
+
     pub fn update(&mut self, _window: &mut Window) -> Result<()> {
         if self.state != GameState::WaitingInput {
+            self.run_scene_commands()?;
             if self.state == GameState::Running {
-                self.entity_factory()?;
-                self.run_update_systems()?;
-                let flag = self.world.read_resource::<GameStateFlagRes>().flag;
-                if let Some(f) = flag {
-                    match f {
-                        GameStateFlag::Victory => self.victory(),
-                        GameStateFlag::Defeat => self.defeat(),
-                    }?;
+                let steps = self.world.write_resource::<TimeScale>().steps();
+                for _ in 0..steps {
+                    let frozen = self
+                        .world
+                        .write_resource::<CameraEffects>()
+                        .frozen(Duration::from_nanos(16700000));
+                    if frozen {
+                        continue;
+                    }
+                    self.world.write_resource::<SessionStats>().elapsed +=
+                        Duration::from_nanos(16700000);
+                    self.entity_factory()?;
+                    self.run_update_systems()?;
+                    let flag = self.world.read_resource::<GameStateFlagRes>().flag;
+                    if let Some(f) = flag {
+                        match f {
+                            GameStateFlag::Victory => self.victory(),
+                            GameStateFlag::Defeat => self.defeat(),
+                        }?;
+                        break;
+                    }
+                    self.maintain();
                 }
             }
+            self.profile("CameraEffectsSystem", |res| {
+                CameraEffectsSystem.run_now(res)
+            });
             self.music_player.update()?;
-            self.world.maintain();
+            self.maintain();
         }
         Ok(())
     }
@@ -159,45 +295,150 @@ impl Scene {
     pub fn draw(&mut self, window: &mut Window) -> Result<()> {
         window.clear(Color::WHITE)?;
 
-        let loaded_assets = self.has_loaded_atlas()? && self.has_loaded_font()?;
+        let loaded_assets = self.has_loaded_atlas()?
+            && self.has_loaded_font()?
+            && self.has_loaded_map()?
+            && self.has_loaded_strings()?;
         if !loaded_assets {
             return Ok(());
         } else if loaded_assets && self.state == GameState::WaitingInput {
-            return self.font.borrow_mut().execute(|font| {
-                let rendered_label = font.render(
-                    "Press ENTER to start...",
-                    &FontStyle::new(72.0, Color::BLACK),
-                )?;
-                window.draw(
-                    &rendered_label.area().with_center((400, 300)),
-                    Img(&rendered_label),
-                );
-                Ok(())
-            });
+            resolve_images(
+                &self.atlas,
+                &self.sprite_images,
+                &self.world.read_resource::<SpriteTable>(),
+            );
+            let mut cache = self.label_cache.borrow_mut();
+            let localization = self.world.read_resource::<Localization>();
+            let images = self.sprite_images.borrow();
+            let ui = &self.ui;
+            return self
+                .font
+                .borrow_mut()
+                .execute(|font| ui.draw(window, font, &mut cache, &localization, &images));
         } else if loaded_assets && self.state == GameState::Initialiazing {
             log::debug!("Starting game...");
+            self.create_map()?;
             self.state = GameState::Running;
         }
 
-        RenderSystem::new(window, Rc::clone(&self.atlas))?.run_now(&self.world.res);
+        let (screen_view, world_view, screen_area, flash) = {
+            let camera = self.world.read_resource::<Camera>();
+            let effects = self.world.read_resource::<CameraEffects>();
+            (
+                camera.screen_view(),
+                camera.shaken_view(effects.shake),
+                Rectangle::new(Vector::ZERO, camera.viewport),
+                effects.flash_color(),
+            )
+        };
+        window.set_view(screen_view);
+        self.profile("BackgroundRenderSystem", |res| -> Result<()> {
+            BackgroundRenderSystem::new(
+                window,
+                Rc::clone(&self.atlas),
+                Rc::clone(&self.sprite_images),
+            )?
+            .run_now(res);
+            window.flush()
+        })?;
+        window.set_view(world_view);
+        self.profile("RenderSystem::World", |res| -> Result<()> {
+            RenderSystem::new(
+                window,
+                Rc::clone(&self.atlas),
+                Rc::clone(&self.sprite_images),
+                RenderPass::World,
+            )?
+            .run_now(res);
+            window.flush()
+        })?;
+        window.set_view(screen_view);
         if self.state == GameState::Running {
             self.update_labels(window)?;
         }
-        if self.state == GameState::Running || self.state == GameState::Paused {
-            LabelRenderSystem::new(window, Rc::clone(&self.font))?.run_now(&self.world.res);
+        let show_labels = self.state == GameState::Running || self.state == GameState::Paused;
+        self.profile("RenderSystem::Screen", |res| -> Result<()> {
+            let mut render = RenderSystem::new(
+                window,
+                Rc::clone(&self.atlas),
+                Rc::clone(&self.sprite_images),
+                RenderPass::Screen,
+            )?;
+            if show_labels {
+                render = render.with_labels(Rc::clone(&self.font), Rc::clone(&self.label_cache));
+            }
+            render.run_now(res);
+            Ok(())
+        })?;
+        if self.state == GameState::GameOver {
+            self.draw_results(window)?;
         }
-        self.world.maintain();
+        #[cfg(any(debug_assertions, feature = "dev-tools"))]
+        self.debug_overlay
+            .draw(&self.world, window, &self.font, world_view, screen_view)?;
+        #[cfg(any(debug_assertions, feature = "dev-tools"))]
+        self.console.draw(window, &self.font, screen_area)?;
+        if self.world.read_resource::<Profiler>().overlay {
+            self.profile("ProfilerOverlay", |res| -> Result<()> {
+                let profiler = res.fetch::<Profiler>();
+                let mut cache = self.profiler_cache.borrow_mut();
+                self.font
+                    .borrow_mut()
+                    .execute(|font| draw_profiler(&profiler, window, font, &mut cache))
+            })?;
+        }
+        if let Some(flash) = flash {
+            window.draw(&screen_area, Col(flash));
+        }
+        self.maintain();
+        self.end_frame();
         Ok(())
     }
 
     pub fn event(&mut self, event: &Event, window: &mut Window) -> Result<()> {
+        #[cfg(any(debug_assertions, feature = "dev-tools"))]
+        {
+            let in_game = self.state == GameState::Running || self.state == GameState::Paused;
+            if in_game && self.console.event(event, &mut self.world) {
+                return Ok(());
+            }
+        }
         match self.state {
-            GameState::WaitingInput => match event {
-                Event::Key(Key::Return, ButtonState::Pressed) => {
-                    self.state = GameState::Initialiazing;
+            GameState::WaitingInput => {
+                let ui_event = self
+                    .ui
+                    .event(event, &mut self.world.write_resource::<SpriteTable>());
+                match ui_event {
+                    Some(UiEvent::Action(UiAction::Start)) => {
+                        self.ui.close();
+                        self.state = GameState::Initialiazing;
+                    }
+                    Some(UiEvent::Action(UiAction::Quit)) => {
+                        log::debug!("Closing window");
+                        window.close();
+                    }
+                    Some(UiEvent::Changed(ref id, UiValue::Bool(enabled)))
+                        if id == "camera_effects" =>
+                    {
+                        self.config.camera_effects.enabled = enabled;
+                        self.world.write_resource::<CameraEffects>().config.enabled = enabled;
+                    }
+                    Some(UiEvent::Changed(ref id, UiValue::Number(volume)))
+                        if id == "music_volume" =>
+                    {
+                        self.config.music_volume = volume;
+                        self.music_player.set_volume(volume);
+                    }
+                    Some(UiEvent::Changed(ref id, UiValue::Index(index)))
+                        if id == "game_speed" && index < GAME_SPEEDS.len() =>
+                    {
+                        self.config.game_speed = GAME_SPEEDS[index].1;
+                        self.world.write_resource::<TimeScale>().scale = self.config.game_speed;
+                    }
+                    Some(ui_event) => log::debug!("Unhandled UI event {:?}", ui_event),
+                    None => {}
                 }
-                _ => {}
-            },
+            }
             GameState::Running | GameState::Paused => {
                 let mut pressed_keys = self.world.write_resource::<PressedKeys>();
                 let pressed_keys = &mut pressed_keys.pressed_keys;
@@ -232,6 +473,29 @@ impl Scene {
                     | Event::GamepadButton(_, GamepadButton::DpadRight, ButtonState::Released) => {
                         pressed_keys.remove(KeyboardKeys::KeyRight as u32);
                     }
+                    #[cfg(any(debug_assertions, feature = "dev-tools"))]
+                    Event::Key(Key::F3, ButtonState::Pressed) => {
+                        self.debug_overlay.toggle();
+                    }
+                    #[cfg(any(debug_assertions, feature = "dev-tools"))]
+                    Event::Key(Key::F4, ButtonState::Pressed) => {
+                        let mut profiler = self.world.write_resource::<Profiler>();
+                        profiler.overlay = !profiler.overlay;
+                    }
+                    #[cfg(any(debug_assertions, feature = "dev-tools"))]
+                    Event::Key(Key::F5, ButtonState::Pressed) => {
+                        let mut profiler = self.world.write_resource::<Profiler>();
+                        match profiler.stop_trace() {
+                            Some(json) => match &self.config.trace_path {
+                                Some(path) => export_chrome_trace(&json, path),
+                                None => log::warn!("No trace path configured, trace dropped"),
+                            },
+                            None => {
+                                log::info!("Capturing a trace, press F5 again to save it");
+                                profiler.start_trace();
+                            }
+                        }
+                    }
                     Event::Key(Key::P, ButtonState::Pressed)
                     | Event::Key(Key::Pause, ButtonState::Pressed)
                     | Event::GamepadButton(_, GamepadButton::Start, ButtonState::Pressed) => {
@@ -252,12 +516,23 @@ impl Scene {
                 }
             }
             GameState::GameOver => {
-                if let Event::Key(Key::Escape, ButtonState::Pressed)
-                | Event::Key(Key::Return, ButtonState::Pressed)
-                | Event::GamepadButton(_, GamepadButton::Start, ButtonState::Pressed) = event
-                {
-                    log::debug!("Closing window");
-                    window.close();
+                let ui_event = self
+                    .ui
+                    .event(event, &mut self.world.write_resource::<SpriteTable>());
+                match ui_event {
+                    Some(UiEvent::Action(UiAction::Custom(ref action))) if action == "retry" => {
+                        self.restart()?;
+                    }
+                    Some(UiEvent::Action(UiAction::Custom(ref action))) if action == "title" => {
+                        self.return_to_title()?;
+                    }
+                    Some(UiEvent::Action(UiAction::Back)) => self.return_to_title()?,
+                    Some(UiEvent::Action(UiAction::Quit)) => {
+                        log::debug!("Closing window");
+                        window.close();
+                    }
+                    Some(ui_event) => log::debug!("Unhandled UI event {:?}", ui_event),
+                    None => {}
                 }
             }
             _ => {}
@@ -265,13 +540,66 @@ impl Scene {
         Ok(())
     }
 
+    /// Runs `run`, recording how long it took in the `Profiler`.
+    fn profile<R>(&self, name: &'static str, run: impl FnOnce(&Resources) -> R) -> R {
+        let start = Instant::now();
+        let result = run(&self.world.res);
+        let end = Instant::now();
+        self.world.write_resource::<Profiler>().record(
+            name,
+            start.duration_since(self.profiler_origin.clone()),
+            end.duration_since(start),
+        );
+        result
+    }
+
+    fn maintain(&mut self) {
+        let start = Instant::now();
+        self.world.maintain();
+        let end = Instant::now();
+        self.world.write_resource::<Profiler>().record(
+            "World::maintain",
+            start.duration_since(self.profiler_origin.clone()),
+            end.duration_since(start),
+        );
+    }
+
+    fn end_frame(&mut self) {
+        let now = Instant::now();
+        if let Some(last) = self.last_frame.replace(now.clone()) {
+            let entity_count = self.world.entities().join().count();
+            self.world
+                .write_resource::<Profiler>()
+                .end_frame(now.duration_since(last), entity_count);
+        }
+    }
+
     fn run_update_systems(&mut self) -> Result<()> {
-        HeroControlSystem.run_now(&self.world.res);
-        WalkSystem.run_now(&self.world.res);
-        FireballSystem.run_now(&self.world.res);
-        CollisionSystem.run_now(&self.world.res);
-        OutOfBoundsSystem.run_now(&self.world.res);
-        HeroBlinkingSystem.run_now(&self.world.res);
+        self.profile("HeroControlSystem", |res| HeroControlSystem.run_now(res));
+        self.profile("WalkSystem", |res| WalkSystem.run_now(res));
+        self.profile("PhysicsSystem", |res| PhysicsSystem.run_now(res));
+        self.profile("FireballSystem", |res| FireballSystem.run_now(res));
+        self.profile("TweenSystem", |res| TweenSystem.run_now(res));
+        self.profile("SpatialGridSystem", |res| SpatialGridSystem.run_now(res));
+        self.profile("CollisionSystem", |res| CollisionSystem.run_now(res));
+        self.profile("OutOfBoundsSystem", |res| OutOfBoundsSystem.run_now(res));
+        self.profile("LifetimeSystem", |res| LifetimeSystem.run_now(res));
+        self.profile("HurtSystem", |res| HurtSystem.run_now(res));
+        self.profile("DyingSystem", |res| DyingSystem.run_now(res));
+        self.profile("ParticleSystem", |res| ParticleSystem.run_now(res));
+        self.profile("HeroBlinkingSystem", |res| HeroBlinkingSystem.run_now(res));
+        self.profile("ActiveEffectsSystem", |res| {
+            ActiveEffectsSystem.run_now(res)
+        });
+        self.profile("FacingSystem", |res| FacingSystem.run_now(res));
+        self.profile("AnimationStateSystem", |res| {
+            AnimationStateSystem.run_now(res)
+        });
+        self.profile("AnimationSystem", |res| AnimationSystem.run_now(res));
+        self.profile("CameraSystem", |res| CameraSystem.run_now(res));
+        self.profile("BackgroundScrollSystem", |res| {
+            BackgroundScrollSystem.run_now(res)
+        });
         Ok(())
     }
 
@@ -287,7 +615,12 @@ impl Scene {
                 if self.cycle_counter == self.config.boss_cycle {
                     self.music_player
                         .play_music(self.config.boss_music.clone())?;
-                    crate::enemy::create_boss(&mut self.world, self.config.boss_config.clone());
+                    let mut boss_config = self.config.boss_config.clone();
+                    if let Some(position) = self.boss_position {
+                        boss_config.position = position;
+                    }
+                    crate::enemy::create_boss(&mut self.world, boss_config);
+                    self.world.write_resource::<SessionStats>().boss_spawned();
                 } else {
                     self.entity_factory.create_entity(&mut self.world)?;
                 }
@@ -296,10 +629,59 @@ impl Scene {
         Ok(())
     }
 
+    fn run_scene_commands(&mut self) -> Result<()> {
+        let pending = std::mem::replace(
+            &mut self.world.write_resource::<SceneCommands>().pending,
+            Vec::new(),
+        );
+        for command in pending {
+            match command {
+                SceneCommand::SkipToBoss => self.skip_to_boss(),
+                SceneCommand::ReloadConfig => {
+                    self.reloading = Some(Asset::new(load_config(SCENE_CONFIG_PATH.to_string())));
+                }
+            }
+        }
+        let mut reloaded = None;
+        if let Some(reloading) = self.reloading.as_mut() {
+            reloading.execute(|config| {
+                reloaded = Some(config.take());
+                Ok(())
+            })?;
+        }
+        if let Some(config) = reloaded {
+            self.reloading = None;
+            if let Some(config) = config {
+                self.apply_config(config)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Makes the next factory cycle the boss one.
+    fn skip_to_boss(&mut self) {
+        if self.cycle_counter < self.config.boss_cycle {
+            let cycle = self.config.new_body_cycle;
+            self.cycle_counter = self.config.boss_cycle - 1;
+            self.cycle_timer = (self.cycle_timer / cycle + 1) * cycle - 1;
+        }
+    }
+
+    /// Applies a reloaded config to the running scene. Entities already created keep their
+    /// settings, the map, world bounds, camera and assets are left as they are.
+    fn apply_config(&mut self, config: SceneConfig) -> Result<()> {
+        add_config_resources(&mut self.world, &config);
+        self.music_player.set_volume(config.music_volume);
+        self.entity_factory = EntityFactory::new(config.entity_factory_config.clone())?;
+        self.config = config;
+        log::info!("Reloaded the scene config");
+        Ok(())
+    }
+
     fn defeat(&mut self) -> Result<()> {
         log::debug!("Player has been defeated");
-        self.end_game()?;
-        create_background(&mut self.world, self.config.defeat_background.clone());
+        self.end_game(Outcome::Defeat)?;
+        create_background(&mut self.world, &self.config.defeat_background);
         self.music_player
             .play_music(self.config.game_over_music.clone())?;
         Ok(())
@@ -307,22 +689,140 @@ impl Scene {
 
     fn victory(&mut self) -> Result<()> {
         log::debug!("Player is victorious");
-        self.end_game()?;
-        create_background(&mut self.world, self.config.victory_background.clone());
+        self.end_game(Outcome::Victory)?;
+        create_background(&mut self.world, &self.config.victory_background);
         self.music_player
             .play_music(self.config.victory_music.clone())?;
         Ok(())
     }
 
-    fn end_game(&mut self) -> Result<()> {
+    fn end_game(&mut self, outcome: Outcome) -> Result<()> {
+        // The hero may have been deleted this frame, but it's still joinable until `maintain`.
+        let score = (&self.world.read_storage::<Hero>())
+            .join()
+            .next()
+            .map_or(0, |hero| hero.score);
+        let mut stats = std::mem::replace(
+            &mut *self.world.write_resource::<SessionStats>(),
+            SessionStats::default(),
+        );
+        stats.finish(outcome, score);
+        if let Some(dir) = &self.config.stats_dir {
+            export_session(&stats, dir);
+        }
+        self.sessions.push(stats);
         self.world.delete_all();
         self.state = GameState::GameOver;
+        self.ui.open(
+            &self.config.ui.game_over_screen,
+            &mut self.world.write_resource::<SpriteTable>(),
+        );
+        Ok(())
+    }
+
+    /// Starts a new run right away, keeping the loaded assets.
+    fn restart(&mut self) -> Result<()> {
+        log::debug!("Restarting the game");
+        self.rebuild_world()?;
+        self.state = GameState::Initialiazing;
+        Ok(())
+    }
+
+    fn return_to_title(&mut self) -> Result<()> {
+        log::debug!("Returning to the title screen");
+        self.rebuild_world()?;
+        self.ui.open(
+            &self.config.ui.title_screen,
+            &mut self.world.write_resource::<SpriteTable>(),
+        );
+        self.state = GameState::WaitingInput;
+        Ok(())
+    }
+
+    /// Replaces the world with a fresh one built from the config. The sprite table is carried
+    /// over since the resolved sprite images are indexed by its handles.
+    fn rebuild_world(&mut self) -> Result<()> {
+        let (mut world, hero) = create_run_world(&self.config);
+        let sprites = std::mem::replace(
+            &mut *self.world.write_resource::<SpriteTable>(),
+            SpriteTable::default(),
+        );
+        world.add_resource(sprites);
+        world.add_resource(self.world.read_resource::<Localization>().clone());
+        let profiler = std::mem::replace(
+            &mut *self.world.write_resource::<Profiler>(),
+            Profiler::default(),
+        );
+        world.add_resource(profiler);
+        self.world = world;
+        self.hero = hero;
+        self.entity_factory = EntityFactory::new(self.config.entity_factory_config.clone())?;
+        self.boss_position = None;
+        self.cycle_timer = 0;
+        self.cycle_counter = 0;
+        self.ui.close();
         Ok(())
     }
 
+    /// Outcome and score of the last run, over the game over background.
+    fn draw_results(&self, window: &mut Window) -> Result<()> {
+        let stats = match self.sessions.last() {
+            Some(stats) => stats,
+            None => return Ok(()),
+        };
+        let mut cache = self.label_cache.borrow_mut();
+        let localization = self.world.read_resource::<Localization>();
+        let images = self.sprite_images.borrow();
+        let ui = &self.ui;
+        let title = match stats.outcome {
+            Some(Outcome::Victory) => localization.get("results.victory").to_string(),
+            _ => localization.get("results.defeat").to_string(),
+        };
+        let score = localization.format(
+            "results.score",
+            &[("value", &stats.final_score.to_string())],
+        );
+        // Above the menu, at a fifth and a third of the screen height.
+        let viewport = self.world.read_resource::<Camera>().viewport;
+        let x = viewport.x / 2.0;
+        let lines = [
+            (title, 72.0, Vector::new(x, viewport.y * 0.2)),
+            (score, 48.0, Vector::new(x, viewport.y * 0.32)),
+        ];
+        self.font.borrow_mut().execute(|font| {
+            for (text, size, center) in &lines {
+                let style = FontStyle::new(*size, Color::BLACK);
+                let label = cache.get_or_render(text, &style, || font.render(text, &style))?;
+                window.draw(&label.area().with_center(*center), Img(label));
+            }
+            ui.draw(window, font, &mut cache, &localization, &images)
+        })
+    }
+
     fn update_labels(&mut self, window: &Window) -> Result<()> {
         let hero_storage = self.world.read_storage::<Hero>();
+        let effects_storage = self.world.read_storage::<ActiveEffects>();
+        let localization = self.world.read_resource::<Localization>();
         if let Some(hero) = hero_storage.get(self.hero) {
+            let active_effects = effects_storage
+                .get(self.hero)
+                .map(|effects| {
+                    effects
+                        .effects
+                        .iter()
+                        .map(|effect| {
+                            localization.format(
+                                "hud.effect",
+                                &[
+                                    ("name", localization.get(effect.kind.string_key())),
+                                    ("seconds", &(effect.remaining.as_secs() + 1).to_string()),
+                                ],
+                            )
+                        })
+                        .collect::<Vec<String>>()
+                        .join("  ")
+                })
+                .unwrap_or_default();
             let mut dict = self.world.write_resource::<VariableDictionary>();
             *dict = VariableDictionary {
                 dictionary: [
@@ -334,8 +834,9 @@ impl Scene {
                     (LabelVariable::Score, format!("{}", hero.score)),
                     (
                         LabelVariable::EngineVersion,
-                        format!("v{}", env!("CARGO_PKG_VERSION")),
+                        env!("CARGO_PKG_VERSION").to_string(),
                     ),
+                    (LabelVariable::ActiveEffects, active_effects),
                 ]
                 .iter()
                 .cloned()
@@ -357,6 +858,74 @@ impl Scene {
         Ok(loaded_atlas)
     }
 
+    fn has_loaded_map(&mut self) -> Result<bool> {
+        let mut loaded_map =
+            self.state != GameState::WaitingInput && self.state != GameState::Initialiazing;
+        if !loaded_map {
+            match &mut self.map {
+                Some(map) => map.execute(|_| {
+                    loaded_map = true;
+                    Ok(())
+                })?,
+                None => loaded_map = true,
+            }
+        }
+        Ok(loaded_map)
+    }
+
+    fn create_map(&mut self) -> Result<()> {
+        if let (Some(map), Some(map_config)) = (&mut self.map, &self.config.map) {
+            let world = &mut self.world;
+            let mut spawns = None;
+            map.execute(|map| {
+                if let Some(map) = map {
+                    spawns = Some(crate::tilemap::create_map(world, map, map_config));
+                }
+                Ok(())
+            })?;
+            if let Some(spawns) = spawns {
+                if let Some(hero_start) = spawns.hero_start {
+                    if let Some(pos) = self.world.write_storage::<Position>().get_mut(self.hero) {
+                        pos.position = hero_start;
+                    }
+                }
+                let hero_position = self
+                    .world
+                    .read_storage::<Position>()
+                    .get(self.hero)
+                    .map(|pos| pos.position);
+                if let Some(position) = hero_position {
+                    let bounds = *self.world.read_resource::<WorldBounds>();
+                    self.world
+                        .write_resource::<Camera>()
+                        .snap_to(position, &bounds);
+                }
+                self.boss_position = spawns.boss_position;
+            }
+        }
+        Ok(())
+    }
+
+    /// Moves the string tables into the world once they're loaded.
+    fn has_loaded_strings(&mut self) -> Result<bool> {
+        let mut loaded = None;
+        match &mut self.strings {
+            Some(strings) => strings.execute(|localization| {
+                loaded = Some(localization.clone());
+                Ok(())
+            })?,
+            None => return Ok(true),
+        }
+        match loaded {
+            Some(localization) => {
+                self.world.add_resource(localization);
+                self.strings = None;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
     fn has_loaded_font(&mut self) -> Result<bool> {
         let mut loaded_font =
             self.state != GameState::WaitingInput && self.state != GameState::Initialiazing;
@@ -370,10 +939,80 @@ impl Scene {
     }
 }
 
+/// Empty world with every component registered and the resources of the scene config.
+pub fn create_world(config: &SceneConfig) -> World {
+    let mut world = World::new();
+    register_components(&mut world);
+    add_resources(&mut world);
+    add_config_resources(&mut world, config);
+    world.add_resource(config.world_bounds);
+    world.add_resource(Camera::new(config.camera_config));
+    world
+}
+
+/// Builds the world of a new run: resources, backgrounds, HUD labels and the hero.
+fn create_run_world(config: &SceneConfig) -> (World, Entity) {
+    let mut world = create_world(config);
+
+    create_background(&mut world, &config.main_background);
+    create_ambient_particles(&mut world, &config.ambient_particles);
+    create_label(
+        &mut world,
+        LabelVariable::FramesPerSecond,
+        "hud.fps",
+        FontStyle::new(48.0, Color::BLACK),
+        Vector::new(20, 587),
+    );
+    create_label(
+        &mut world,
+        LabelVariable::HeroLives,
+        "hud.lives",
+        FontStyle::new(48.0, Color::BLACK),
+        Vector::new(10, 20),
+    );
+    create_label(
+        &mut world,
+        LabelVariable::Score,
+        "hud.score",
+        FontStyle::new(48.0, Color::BLACK),
+        Vector::new(730, 20),
+    );
+    create_label(
+        &mut world,
+        LabelVariable::EngineVersion,
+        "hud.version",
+        FontStyle::new(48.0, Color::BLACK),
+        Vector::new(730, 587),
+    );
+    create_label(
+        &mut world,
+        LabelVariable::ActiveEffects,
+        "hud.effects",
+        FontStyle::new(32.0, Color::BLACK),
+        Vector::new(400, 20),
+    );
+    let hero = crate::hero::create_hero(&mut world, config.hero_config.clone());
+    let hero_position = world
+        .read_storage::<Position>()
+        .get(hero)
+        .map(|pos| pos.position);
+    {
+        let mut camera = world.write_resource::<Camera>();
+        camera.follow(hero);
+        if let Some(position) = hero_position {
+            camera.snap_to(position, &config.world_bounds);
+        }
+    }
+    (world, hero)
+}
+
 fn register_components(world: &mut World) {
     world.register::<Position>();
     world.register::<Velocity>();
     world.register::<Render>();
+    world.register::<Collider>();
+    world.register::<CollisionLayer>();
+    world.register::<ContinuousCollision>();
     world.register::<Shooter>();
     world.register::<Label>();
     world.register::<Hero>();
@@ -382,11 +1021,23 @@ fn register_components(world: &mut World) {
     world.register::<Enemy>();
     world.register::<Healing>();
     world.register::<Background>();
-    world.register::<CalculateOutOfBounds>();
+    world.register::<OutOfBounds>();
+    world.register::<Lifetime>();
+    world.register::<Dying>();
+    world.register::<Hurt>();
+    world.register::<ParticleEmitter>();
+    world.register::<Particle>();
+    world.register::<Tween>();
     world.register::<Fireball>();
+    world.register::<PowerUp>();
+    world.register::<ActiveEffects>();
+    world.register::<Animation>();
+    world.register::<SpriteTransform>();
+    world.register::<RigidBody>();
+    world.register::<MapTile>();
 }
 
-fn add_resorces(world: &mut World) {
+fn add_resources(world: &mut World) {
     world.add_resource(GameStateFlagRes { flag: None });
     world.add_resource(VariableDictionary {
         dictionary: [
@@ -395,8 +1046,9 @@ fn add_resorces(world: &mut World) {
             (LabelVariable::Score, "0".to_string()),
             (
                 LabelVariable::EngineVersion,
-                format!("v{}", env!("CARGO_PKG_VERSION")),
+                env!("CARGO_PKG_VERSION").to_string(),
             ),
+            (LabelVariable::ActiveEffects, String::new()),
         ]
         .iter()
         .cloned()
@@ -405,25 +1057,67 @@ fn add_resorces(world: &mut World) {
     world.add_resource(PressedKeys {
         pressed_keys: BitSet::new(),
     });
+    world.add_resource(TileCollisionMap::default());
+    world.add_resource(SpatialGrid::default());
+    world.add_resource(WorldBounds::default());
+    world.add_resource(Camera::default());
+    world.add_resource(CameraEffects::default());
+    world.add_resource(CollisionRules::default());
+    world.add_resource(RenderLayers::default());
+    world.add_resource(ParticleEffects::default());
+    world.add_resource(TweenEvents::default());
+    world.add_resource(SpriteTable::default());
+    world.add_resource(SceneCommands::default());
+    world.add_resource(Profiler::default());
+    world.add_resource(SessionStats::default());
+    world.add_resource(Localization::default());
 }
 
-fn create_background(world: &mut World, sprite: String) -> Entity {
-    world
-        .create_entity()
-        .with(Background)
-        .with(Position {
-            position: Vector::new(400, 300),
-        })
-        .with(Render {
-            sprite,
-            bounding_box: None,
-        })
-        .build()
+/// Resources taken from the scene config, added again when it's reloaded.
+fn add_config_resources(world: &mut World, config: &SceneConfig) {
+    world.add_resource(config.physics_config);
+    world.add_resource(CameraEffects::new(config.camera_effects.clone()));
+    world.add_resource(CollisionRules {
+        rules: config.collision_rules.clone(),
+    });
+    world.add_resource(RenderLayers {
+        order: config.render_layers.clone(),
+        batched: config.batched_layers.clone(),
+    });
+    world.add_resource(ParticleEffects::new(config.particle_effects.clone()));
+    world.add_resource(config.prefabs.clone());
+    world.add_resource(TimeScale::new(config.game_speed));
+}
+
+/// Resolves to `None` when the config can't be read, so a bad edit doesn't stop the game.
+fn load_config(path: String) -> impl Future<Item = Option<SceneConfig>, Error = Error> {
+    load_file(path).then(|data| {
+        let config = data.map_err(|error| error.to_string()).and_then(|data| {
+            serde_json::from_slice(data.as_slice()).map_err(|error| error.to_string())
+        });
+        if let Err(ref error) = config {
+            log::warn!("Couldn't load the scene config: {}", error);
+        }
+        Ok(config.ok())
+    })
+}
+
+fn load_map(path: String) -> impl Future<Item = Option<TiledMap>, Error = Error> {
+    load_file(path).then(|data| {
+        let map = data.map_err(|error| error.to_string()).and_then(|data| {
+            serde_json::from_slice(data.as_slice()).map_err(|error| error.to_string())
+        });
+        if let Err(ref error) = map {
+            log::warn!("Couldn't load the map: {}", error);
+        }
+        Ok(map.ok())
+    })
 }
 
 fn create_label(
     world: &mut World,
     variable: LabelVariable,
+    template: &'static str,
     font_style: FontStyle,
     position: Vector,
 ) -> Entity {
@@ -431,7 +1125,10 @@ fn create_label(
         .create_entity()
         .with(Label {
             bind_variable: variable,
+            template,
             font_style,
+            layer: RenderLayer::Hud,
+            z: 0.0,
         })
         .with(Position { position })
         .build()
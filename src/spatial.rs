@@ -0,0 +1,101 @@
+use std::collections::HashMap;
+
+use quicksilver::geom::{Rectangle, Shape, Vector};
+use specs::Entity;
+
+/// Uniform grid bucketing entities by the cells their bounds touch, rebuilt every tick.
+pub struct SpatialGrid {
+    cell_size: f32,
+    cells: HashMap<(i32, i32), Vec<Entity>>,
+}
+
+impl Default for SpatialGrid {
+    fn default() -> SpatialGrid {
+        SpatialGrid::new(128.0)
+    }
+}
+
+impl SpatialGrid {
+    pub fn new(cell_size: f32) -> SpatialGrid {
+        SpatialGrid {
+            cell_size,
+            cells: HashMap::new(),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.clear();
+    }
+
+    pub fn insert(&mut self, entity: Entity, area: Rectangle) {
+        let ((x0, y0), (x1, y1)) = self.cell_range(area);
+        for x in x0..=x1 {
+            for y in y0..=y1 {
+                self.cells
+                    .entry((x, y))
+                    .or_insert_with(Vec::new)
+                    .push(entity);
+            }
+        }
+    }
+
+    /// Entities sharing at least one cell with `area`, each reported once.
+    pub fn query(&self, area: Rectangle) -> Vec<Entity> {
+        let ((x0, y0), (x1, y1)) = self.cell_range(area);
+        let mut found = Vec::new();
+        for x in x0..=x1 {
+            for y in y0..=y1 {
+                if let Some(cell) = self.cells.get(&(x, y)) {
+                    found.extend(cell.iter().cloned());
+                }
+            }
+        }
+        found.sort_by_key(|entity| entity.id());
+        found.dedup();
+        found
+    }
+
+    fn cell_range(&self, area: Rectangle) -> ((i32, i32), (i32, i32)) {
+        let cell = |point: Vector| {
+            (
+                (point.x / self.cell_size).floor() as i32,
+                (point.y / self.cell_size).floor() as i32,
+            )
+        };
+        (cell(area.top_left()), cell(area.top_left() + area.size()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use specs::World;
+
+    fn entities(count: usize) -> Vec<Entity> {
+        let world = World::new();
+        (0..count).map(|_| world.entities().create()).collect()
+    }
+
+    #[test]
+    fn query_reports_each_entity_once() {
+        let entities = entities(2);
+        let mut grid = SpatialGrid::new(10.0);
+        grid.insert(entities[0], Rectangle::new((5, 5), (20, 20)));
+        grid.insert(entities[1], Rectangle::new((100, 100), (5, 5)));
+        assert_eq!(
+            grid.query(Rectangle::new((0, 0), (30, 30))),
+            vec![entities[0]]
+        );
+        assert!(grid.query(Rectangle::new((50, 50), (5, 5))).is_empty());
+    }
+
+    #[test]
+    fn clear_drops_every_cell() {
+        let entities = entities(1);
+        let mut grid = SpatialGrid::new(10.0);
+        grid.insert(entities[0], Rectangle::new((0, 0), (45, 45)));
+        grid.clear();
+        assert!(grid.cells.is_empty());
+        assert!(grid.query(Rectangle::new((0, 0), (45, 45))).is_empty());
+    }
+}
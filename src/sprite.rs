@@ -0,0 +1,96 @@
+use std::collections::HashMap;
+
+use quicksilver::graphics::{Atlas, Image};
+use serde::{Deserialize, Serialize};
+use specs::World;
+
+/// Index of a sprite name in the `SpriteTable`.
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Copy, Clone)]
+pub struct SpriteHandle(pub u32);
+
+/// Sprite names interned into handles when entities are created, so rendering never
+/// touches strings.
+#[derive(Debug, Default)]
+pub struct SpriteTable {
+    names: Vec<String>,
+    handles: HashMap<String, SpriteHandle>,
+}
+
+impl SpriteTable {
+    pub fn intern(&mut self, name: &str) -> SpriteHandle {
+        if let Some(handle) = self.handles.get(name) {
+            return *handle;
+        }
+        let handle = SpriteHandle(self.names.len() as u32);
+        self.names.push(name.to_string());
+        self.handles.insert(name.to_string(), handle);
+        handle
+    }
+
+    pub fn name(&self, handle: SpriteHandle) -> Option<&str> {
+        self.names.get(handle.0 as usize).map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
+
+pub fn intern_sprite(world: &World, name: &str) -> SpriteHandle {
+    world.write_resource::<SpriteTable>().intern(name)
+}
+
+/// Atlas images by handle, looked up once per new handle. Kept by the scene, outside the
+/// world, since images can't be shared between threads.
+#[derive(Default)]
+pub struct SpriteImages {
+    images: Vec<Option<Image>>,
+}
+
+impl SpriteImages {
+    /// Looks up the handles interned since the last call.
+    pub fn resolve(&mut self, table: &SpriteTable, atlas: &mut Atlas) {
+        for index in self.images.len()..table.len() {
+            let name = &table.names[index];
+            let image = atlas.get(name).map(|item| item.unwrap_image());
+            if image.is_none() {
+                log::warn!("Sprite '{}' not found in the atlas", name);
+            }
+            self.images.push(image);
+        }
+    }
+
+    pub fn get(&self, handle: SpriteHandle) -> Option<&Image> {
+        self.images
+            .get(handle.0 as usize)
+            .and_then(|image| image.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interning_the_same_name_returns_the_same_handle() {
+        let mut table = SpriteTable::default();
+        let hero = table.intern("heroi");
+        let fireball = table.intern("fogo");
+        assert_ne!(hero, fireball);
+        assert_eq!(table.intern("heroi"), hero);
+        assert_eq!(table.len(), 2);
+    }
+
+    #[test]
+    fn handles_map_back_to_names() {
+        let mut table = SpriteTable::default();
+        assert!(table.is_empty());
+        let handle = table.intern("potion");
+        assert_eq!(table.name(handle), Some("potion"));
+        assert_eq!(table.name(SpriteHandle(1)), None);
+    }
+}
@@ -0,0 +1,205 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use quicksilver::geom::Vector;
+use serde::Serialize;
+
+use crate::power_up::PowerUpKind;
+
+#[derive(Serialize, Debug, Eq, PartialEq, Copy, Clone)]
+pub enum Outcome {
+    Victory,
+    Defeat,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "type")]
+pub enum GameplayEventKind {
+    EnemyDefeated { enemy: String },
+    HeroDamaged { source: String, lives: i32 },
+    ShieldAbsorbed { source: String },
+    PotionUsed,
+    PowerUpCollected { kind: PowerUpKind },
+    BossSpawned,
+    GameOver { outcome: Outcome },
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct GameplayEvent {
+    pub time_ms: u64,
+    pub position: Option<Vector>,
+    #[serde(flatten)]
+    pub kind: GameplayEventKind,
+}
+
+/// What happened during a run, kept after the world is cleared at game over.
+#[derive(Serialize, Debug, Default, Clone)]
+pub struct SessionStats {
+    /// Game time, paused and frozen frames left out.
+    #[serde(skip)]
+    pub elapsed: Duration,
+    pub enemies_defeated: BTreeMap<String, u32>,
+    pub damage_taken: BTreeMap<String, u32>,
+    pub potions_used: u32,
+    pub power_ups_collected: BTreeMap<String, u32>,
+    pub time_to_boss_ms: Option<u64>,
+    pub time_to_end_ms: Option<u64>,
+    pub outcome: Option<Outcome>,
+    /// Enemies defeated in a row without getting hit.
+    #[serde(skip)]
+    pub combo: u32,
+    pub max_combo: u32,
+    pub final_score: i32,
+    pub events: Vec<GameplayEvent>,
+}
+
+impl SessionStats {
+    fn time_ms(&self) -> u64 {
+        self.elapsed.as_millis() as u64
+    }
+
+    fn log(&mut self, kind: GameplayEventKind, position: Option<Vector>) {
+        self.events.push(GameplayEvent {
+            time_ms: self.time_ms(),
+            position,
+            kind,
+        });
+    }
+
+    pub fn enemy_defeated(&mut self, enemy: &str, position: Option<Vector>) {
+        *self.enemies_defeated.entry(enemy.to_string()).or_insert(0) += 1;
+        self.combo += 1;
+        self.max_combo = self.max_combo.max(self.combo);
+        self.log(
+            GameplayEventKind::EnemyDefeated {
+                enemy: enemy.to_string(),
+            },
+            position,
+        );
+    }
+
+    pub fn hero_damaged(&mut self, source: &str, lives: i32, position: Option<Vector>) {
+        *self.damage_taken.entry(source.to_string()).or_insert(0) += 1;
+        self.combo = 0;
+        self.log(
+            GameplayEventKind::HeroDamaged {
+                source: source.to_string(),
+                lives,
+            },
+            position,
+        );
+    }
+
+    /// A hit taken by the shield, which neither costs a life nor breaks the combo.
+    pub fn shield_absorbed(&mut self, source: &str, position: Option<Vector>) {
+        self.log(
+            GameplayEventKind::ShieldAbsorbed {
+                source: source.to_string(),
+            },
+            position,
+        );
+    }
+
+    pub fn potion_used(&mut self, position: Option<Vector>) {
+        self.potions_used += 1;
+        self.log(GameplayEventKind::PotionUsed, position);
+    }
+
+    pub fn power_up_collected(&mut self, kind: PowerUpKind, position: Option<Vector>) {
+        *self
+            .power_ups_collected
+            .entry(format!("{:?}", kind))
+            .or_insert(0) += 1;
+        self.log(GameplayEventKind::PowerUpCollected { kind }, position);
+    }
+
+    pub fn boss_spawned(&mut self) {
+        if self.time_to_boss_ms.is_none() {
+            self.time_to_boss_ms = Some(self.time_ms());
+        }
+        self.log(GameplayEventKind::BossSpawned, None);
+    }
+
+    pub fn finish(&mut self, outcome: Outcome, final_score: i32) {
+        self.outcome = Some(outcome);
+        self.time_to_end_ms = Some(self.time_ms());
+        self.final_score = final_score;
+        self.log(GameplayEventKind::GameOver { outcome }, None);
+    }
+}
+
+/// Writes the stats to `<dir>/session-<unix time>.json`.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn export_session(stats: &SessionStats, dir: &str) {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs());
+    let path = std::path::Path::new(dir).join(format!("session-{}.json", timestamp));
+    let result = std::fs::create_dir_all(dir)
+        .and_then(|_| Ok(serde_json::to_string_pretty(stats)?))
+        .and_then(|json| std::fs::write(&path, json));
+    match result {
+        Ok(()) => log::info!("Session stats written to {}", path.display()),
+        Err(error) => log::warn!("Couldn't write the session stats: {}", error),
+    }
+}
+
+/// Browsers have no files to write to, the stats stay in `Scene::sessions`.
+#[cfg(target_arch = "wasm32")]
+pub fn export_session(_stats: &SessionStats, _dir: &str) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn damage_resets_the_combo() {
+        let mut stats = SessionStats::default();
+        stats.enemy_defeated("Walker", None);
+        stats.enemy_defeated("Flyer", None);
+        stats.enemy_defeated("Walker", None);
+        stats.hero_damaged("Walker", 2, None);
+        stats.enemy_defeated("Walker", None);
+        assert_eq!(stats.combo, 1);
+        assert_eq!(stats.max_combo, 3);
+        assert_eq!(stats.enemies_defeated["Walker"], 3);
+        assert_eq!(stats.damage_taken["Walker"], 1);
+        assert_eq!(stats.events.len(), 5);
+    }
+
+    #[test]
+    fn shield_keeps_the_combo() {
+        let mut stats = SessionStats::default();
+        stats.enemy_defeated("Walker", None);
+        stats.shield_absorbed("Fireball", None);
+        stats.enemy_defeated("Walker", None);
+        assert_eq!(stats.max_combo, 2);
+        assert!(stats.damage_taken.is_empty());
+    }
+
+    #[test]
+    fn boss_time_is_recorded_once() {
+        let mut stats = SessionStats::default();
+        stats.elapsed = Duration::from_millis(1500);
+        stats.boss_spawned();
+        stats.elapsed = Duration::from_millis(4000);
+        stats.boss_spawned();
+        assert_eq!(stats.time_to_boss_ms, Some(1500));
+        assert_eq!(stats.events.len(), 2);
+    }
+
+    #[test]
+    fn finish_records_the_outcome() {
+        let mut stats = SessionStats::default();
+        stats.elapsed = Duration::from_millis(9000);
+        stats.finish(Outcome::Victory, 1200);
+        assert_eq!(stats.outcome, Some(Outcome::Victory));
+        assert_eq!(stats.time_to_end_ms, Some(9000));
+        assert_eq!(stats.final_score, 1200);
+        match stats.events.last().map(|event| &event.kind) {
+            Some(GameplayEventKind::GameOver { outcome }) => assert_eq!(*outcome, Outcome::Victory),
+            other => panic!("expected a game over event, got {:?}", other),
+        }
+    }
+}
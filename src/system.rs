@@ -1,56 +1,163 @@
-use std::{cell::RefCell, rc::Rc, time::Duration};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    rc::Rc,
+    time::Duration,
+};
 
 use crate::{
+    animation::{AnimationClip, AnimationClipName},
+    bounds::{DespawnPolicy, WorldBounds},
+    camera::{Camera, CameraEffects, CameraEvent},
+    collision::{CollisionAction, CollisionRule, CollisionRules, CollisionTarget},
     component::{
-        Boss, CalculateOutOfBounds, ChangeSprite, Enemy, Fireball, Healing, Hero, Label, Position,
-        Render, Shooter, Velocity,
+        ActiveEffects, Animation, Background, Boss, ChangeSprite, Collider, CollisionLayer,
+        ContinuousCollision, Dying, Enemy, Facing, Fireball, Healing, Hero, Hurt, Label, Lifetime,
+        MapTile, OutOfBounds, Particle, ParticleEmitter, Position, PowerUp, Render, RigidBody,
+        Shooter, SpriteTransform, Tween, Velocity,
     },
     enemy::FireballConfig,
-    resources::{GameStateFlag, GameStateFlagRes, KeyboardKeys, PressedKeys, VariableDictionary},
+    label::LabelCache,
+    localization::Localization,
+    particles::{create_particle, ParticleEffectName, ParticleEffects},
+    physics::PhysicsConfig,
+    power_up::PowerUpKind,
+    resources::{
+        GameStateFlag, GameStateFlagRes, KeyboardKeys, PressedKeys, RenderLayer, RenderLayers,
+        VariableDictionary,
+    },
+    spatial::SpatialGrid,
+    sprite::{SpriteHandle, SpriteImages, SpriteTable},
+    stats::SessionStats,
+    tilemap::TileCollisionMap,
+    tween::{TweenCompletion, TweenEvents, TweenValue},
+};
+
+use specs::{
+    world::Index, Builder, Entities, Entity, Join, LazyUpdate, Read, ReadStorage, System, Write,
+    WriteStorage,
 };
 
-use specs::{Entities, Entity, Join, LazyUpdate, Read, ReadStorage, System, Write, WriteStorage};
+use rand::{thread_rng, Rng};
 
 use quicksilver::{
-    geom::{Shape, Vector},
-    graphics::{Atlas, Background::Img, Font},
+    geom::{Rectangle, Shape, Vector},
+    graphics::{
+        Atlas,
+        Background::{Blended, Img},
+        Font, Image,
+    },
     lifecycle::{Asset, Window},
     Result,
 };
 
+/// Group of entities drawn together; the scene sets the window view before each pass.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum RenderPass {
+    /// Entities in world space, seen through the camera.
+    World,
+    /// `Hud` layer entities drawn over the world.
+    Screen,
+}
+
 pub struct RenderSystem<'a> {
     window: &'a mut Window,
     atlas: Rc<RefCell<Asset<Atlas>>>,
+    images: Rc<RefCell<SpriteImages>>,
+    pass: RenderPass,
+    labels: Option<(Rc<RefCell<Asset<Font>>>, Rc<RefCell<LabelCache>>)>,
+}
+
+/// Room kept around the view when culling, so sprites moved by the screen shake don't pop in.
+const CULL_MARGIN: f32 = 32.0;
+
+/// Looks up in the atlas the sprites interned since the last frame.
+pub(crate) fn resolve_images(
+    atlas: &Rc<RefCell<Asset<Atlas>>>,
+    images: &Rc<RefCell<SpriteImages>>,
+    sprites: &SpriteTable,
+) {
+    atlas
+        .borrow_mut()
+        .execute(|loaded_atlas| {
+            images.borrow_mut().resolve(sprites, loaded_atlas);
+            Ok(())
+        })
+        .unwrap();
 }
 
 impl<'a> RenderSystem<'a> {
     pub fn new(
         window: &'a mut Window,
         atlas: Rc<RefCell<Asset<Atlas>>>,
+        images: Rc<RefCell<SpriteImages>>,
+        pass: RenderPass,
     ) -> Result<RenderSystem<'a>> {
-        Ok(RenderSystem { window, atlas })
+        Ok(RenderSystem {
+            window,
+            atlas,
+            images,
+            pass,
+            labels: None,
+        })
     }
 
-    fn do_render(&mut self, render: &mut Render, sprite: String, position: Vector) -> Result<()> {
-        let window = &mut self.window;
-        self.atlas.borrow_mut().execute(|loaded_atlas| {
-            let image = loaded_atlas.get(&sprite).unwrap().unwrap_image();
-            let area = image.area();
-            render.bounding_box = Some(area);
-            window.draw(&area.with_center(position), Img(&image));
-            Ok(())
-        })
+    /// Also draws the labels of the pass, sorted with the sprites by layer and depth.
+    pub fn with_labels(
+        mut self,
+        font: Rc<RefCell<Asset<Font>>>,
+        cache: Rc<RefCell<LabelCache>>,
+    ) -> RenderSystem<'a> {
+        self.labels = Some((font, cache));
+        self
     }
 
-    fn get_sprite(render: &Render, change_sprite: Option<&ChangeSprite>) -> String {
-        if let Some(change_sprite) = change_sprite {
-            if change_sprite.do_change {
-                change_sprite.new_sprite.clone()
-            } else {
-                render.sprite.clone()
-            }
-        } else {
-            render.sprite.clone()
+    fn in_pass(&self, layer: RenderLayer) -> bool {
+        match self.pass {
+            RenderPass::World => layer != RenderLayer::Hud,
+            RenderPass::Screen => layer == RenderLayer::Hud,
+        }
+    }
+
+    fn is_visible(
+        view: &Rectangle,
+        image: &Image,
+        center: Vector,
+        transform: Option<&SpriteTransform>,
+    ) -> bool {
+        let scale = transform.map_or(1.0, |transform| {
+            transform.scale.x.abs().max(transform.scale.y.abs())
+        });
+        // Half diagonal, so rotated sprites are never culled while in view.
+        let radius = image.area().size.len() / 2.0 * scale + CULL_MARGIN;
+        center.x + radius >= view.pos.x
+            && center.x - radius <= view.pos.x + view.size.x
+            && center.y + radius >= view.pos.y
+            && center.y - radius <= view.pos.y + view.size.y
+    }
+
+    fn do_render(
+        window: &mut Window,
+        image: &Image,
+        center: Vector,
+        transform: Option<&SpriteTransform>,
+    ) {
+        let area = image.area().with_center(center);
+        match transform {
+            Some(transform) => window.draw_ex(
+                &area,
+                Blended(image, transform.color()),
+                transform.transform(),
+                0,
+            ),
+            None => window.draw(&area, Img(image)),
+        }
+    }
+
+    fn get_sprite(render: &Render, change_sprite: Option<&ChangeSprite>) -> SpriteHandle {
+        match change_sprite {
+            Some(change_sprite) if change_sprite.do_change => change_sprite.new_sprite,
+            _ => render.sprite,
         }
     }
 }
@@ -59,76 +166,225 @@ impl<'a> System<'a> for RenderSystem<'a> {
     type SystemData = (
         Entities<'a>,
         ReadStorage<'a, Position>,
-        WriteStorage<'a, Render>,
+        ReadStorage<'a, Render>,
         ReadStorage<'a, Hero>,
         ReadStorage<'a, ChangeSprite>,
+        ReadStorage<'a, SpriteTransform>,
+        Read<'a, RenderLayers>,
+        Read<'a, SpriteTable>,
+        Read<'a, Camera>,
+        ReadStorage<'a, Label>,
+        Read<'a, VariableDictionary>,
+        Read<'a, Localization>,
     );
 
-    fn run(&mut self, (entities, pos, mut render, hero, change_sprite): Self::SystemData) {
-        for (e, pos, render) in (&entities, &pos, &mut render).join() {
-            let hero: Option<&Hero> = hero.get(e);
-            let change_sprite: Option<&ChangeSprite> = change_sprite.get(e);
+    fn run(
+        &mut self,
+        (
+            entities,
+            pos,
+            render,
+            hero,
+            change_sprite,
+            transform,
+            layers,
+            sprites,
+            camera,
+            label,
+            dict,
+            localization,
+        ): Self::SystemData,
+    ) {
+        let mut label_images = Vec::new();
+        if let Some((font, cache)) = &self.labels {
+            let mut cache = cache.borrow_mut();
+            font.borrow_mut()
+                .execute(|font| {
+                    for (e, pos, label) in (&entities, &pos, &label).join() {
+                        if !self.in_pass(label.layer) {
+                            continue;
+                        }
+                        let rank = match layers.rank(label.layer) {
+                            Some(rank) => rank,
+                            None => continue,
+                        };
+                        let value = match dict.dictionary.get(&label.bind_variable) {
+                            Some(value) if !value.is_empty() => value,
+                            _ => continue,
+                        };
+                        let image = cache.get_or_format(
+                            label.template,
+                            value,
+                            &label.font_style,
+                            || {
+                                let text = localization.format(label.template, &[("value", value)]);
+                                font.render(&text, &label.font_style)
+                            },
+                        )?;
+                        let transform = transform.get(e);
+                        let center = pos.position
+                            + transform.map_or(Vector::ZERO, |transform| transform.offset);
+                        label_images.push((rank, label.z, image.clone(), center, transform));
+                    }
+                    Ok(())
+                })
+                .unwrap();
+        }
+
+        resolve_images(&self.atlas, &self.images, &sprites);
+        let images = self.images.borrow();
+        let view = match self.pass {
+            RenderPass::World => camera.view_area(),
+            RenderPass::Screen => Rectangle::new(Vector::ZERO, camera.viewport),
+        };
 
-            if let Some(hero) = hero {
-                if hero.render {
-                    let sprite = RenderSystem::get_sprite(render, change_sprite);
-                    self.do_render(render, sprite, pos.position).unwrap();
+        let mut draws = Vec::new();
+        for (e, pos, render) in (&entities, &pos, &render).join() {
+            if !self.in_pass(render.layer) {
+                continue;
+            }
+            // Layers missing from the scene order are hidden.
+            let rank = match layers.rank(render.layer) {
+                Some(rank) => rank,
+                None => continue,
+            };
+            if let Some(hero) = hero.get(e) {
+                if !hero.render {
+                    continue;
                 }
-            } else {
-                let sprite = RenderSystem::get_sprite(render, change_sprite);
-                self.do_render(render, sprite, pos.position).unwrap();
             }
+            let sprite = RenderSystem::get_sprite(render, change_sprite.get(e));
+            let image = match images.get(sprite) {
+                Some(image) => image,
+                None => continue,
+            };
+            let transform = transform.get(e);
+            let center =
+                pos.position + transform.map_or(Vector::ZERO, |transform| transform.offset);
+            if !RenderSystem::is_visible(&view, image, center, transform) {
+                continue;
+            }
+            let batch = if layers.is_batched(render.layer) {
+                Some(sprite)
+            } else {
+                None
+            };
+            draws.push((rank, render.z, batch, (image, center, transform)));
+        }
+        for (rank, z, image, center, transform) in &label_images {
+            draws.push((*rank, *z, None, (image, *center, *transform)));
+        }
+
+        sort_draws(&mut draws);
+        for (_, _, _, (image, center, transform)) in draws {
+            RenderSystem::do_render(self.window, image, center, transform);
         }
     }
 }
 
-pub struct WalkSystem;
+/// Sorts draws by layer rank and depth, then by sprite on batched layers so draws sharing a
+/// texture are next to each other. The sort is stable, so the rest keep the join order.
+fn sort_draws<T>(draws: &mut [(usize, f32, Option<SpriteHandle>, T)]) {
+    draws.sort_by(|a, b| {
+        a.0.cmp(&b.0)
+            .then(a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+            .then(a.2.cmp(&b.2))
+    });
+}
 
-impl<'a> System<'a> for WalkSystem {
-    type SystemData = (ReadStorage<'a, Velocity>, WriteStorage<'a, Position>);
+/// Draws the parallax background layers in screen space, back to front.
+pub struct BackgroundRenderSystem<'a> {
+    window: &'a mut Window,
+    atlas: Rc<RefCell<Asset<Atlas>>>,
+    images: Rc<RefCell<SpriteImages>>,
+}
 
-    fn run(&mut self, (vel, mut pos): Self::SystemData) {
-        let time_step = 0.0167;
+impl<'a> BackgroundRenderSystem<'a> {
+    pub fn new(
+        window: &'a mut Window,
+        atlas: Rc<RefCell<Asset<Atlas>>>,
+        images: Rc<RefCell<SpriteImages>>,
+    ) -> Result<BackgroundRenderSystem<'a>> {
+        Ok(BackgroundRenderSystem {
+            window,
+            atlas,
+            images,
+        })
+    }
+}
 
-        for (vel, pos) in (&vel, &mut pos).join() {
-            pos.position += vel.velocity * time_step;
+impl<'a> System<'a> for BackgroundRenderSystem<'a> {
+    type SystemData = (
+        Read<'a, Camera>,
+        Read<'a, SpriteTable>,
+        WriteStorage<'a, Background>,
+        ReadStorage<'a, Render>,
+    );
+
+    fn run(&mut self, (camera, sprites, mut background, render): Self::SystemData) {
+        resolve_images(&self.atlas, &self.images, &sprites);
+        let images = self.images.borrow();
+
+        let mut layers = (&mut background, &render).join().collect::<Vec<_>>();
+        layers.sort_by_key(|(background, _)| background.depth);
+
+        let view = camera.view_area();
+        let viewport = camera.viewport;
+        for (background, render) in layers {
+            let image = match images.get(render.sprite) {
+                Some(image) => image,
+                None => continue,
+            };
+            let size = image.area().size;
+            background.width = size.x;
+            let mut center = viewport / 2.0
+                + Vector::new(
+                    background.scroll - view.pos.x * background.scroll_factor,
+                    background.offset_y - view.pos.y * background.scroll_factor,
+                );
+            if background.repeat_x && size.x > 0.0 {
+                let left = center.x - size.x / 2.0;
+                center.x -= size.x * (left / size.x).ceil();
+                while center.x - size.x / 2.0 < viewport.x {
+                    self.window
+                        .draw(&image.area().with_center(center), Img(image));
+                    center.x += size.x;
+                }
+            } else {
+                self.window
+                    .draw(&image.area().with_center(center), Img(image));
+            }
         }
     }
 }
 
-pub struct LabelRenderSystem<'a> {
-    window: &'a mut Window,
-    font: Rc<RefCell<Asset<Font>>>,
-}
+pub struct BackgroundScrollSystem;
+
+impl<'a> System<'a> for BackgroundScrollSystem {
+    type SystemData = WriteStorage<'a, Background>;
+
+    fn run(&mut self, mut background: Self::SystemData) {
+        let time_step = 0.0167;
 
-impl<'a> LabelRenderSystem<'a> {
-    pub fn new(window: &mut Window, font: Rc<RefCell<Asset<Font>>>) -> Result<LabelRenderSystem> {
-        Ok(LabelRenderSystem { window, font })
+        for background in (&mut background).join() {
+            background.scroll += background.auto_scroll * time_step;
+            if background.repeat_x && background.width > 0.0 {
+                background.scroll %= background.width;
+            }
+        }
     }
 }
 
-impl<'a> System<'a> for LabelRenderSystem<'a> {
-    type SystemData = (
-        Read<'a, VariableDictionary>,
-        ReadStorage<'a, Position>,
-        ReadStorage<'a, Label>,
-    );
+pub struct WalkSystem;
 
-    fn run(&mut self, (dict, pos, render): Self::SystemData) {
-        for (pos, render) in (&pos, &render).join() {
-            let window = &mut self.window;
-            self.font
-                .borrow_mut()
-                .execute(|font| {
-                    let rendered_label =
-                        font.render(&dict.dictionary[&render.bind_variable], &render.font_style)?;
-                    window.draw(
-                        &rendered_label.area().with_center(pos.position),
-                        Img(&rendered_label),
-                    );
-                    Ok(())
-                })
-                .unwrap();
+impl<'a> System<'a> for WalkSystem {
+    type SystemData = (ReadStorage<'a, Velocity>, WriteStorage<'a, Position>);
+
+    fn run(&mut self, (vel, mut pos): Self::SystemData) {
+        let time_step = 0.0167;
+
+        for (vel, pos) in (&vel, &mut pos).join() {
+            pos.position += vel.velocity * time_step;
         }
     }
 }
@@ -138,27 +394,61 @@ pub struct HeroControlSystem;
 impl<'a> System<'a> for HeroControlSystem {
     type SystemData = (
         Read<'a, PressedKeys>,
+        Read<'a, PhysicsConfig>,
+        Read<'a, WorldBounds>,
         WriteStorage<'a, Hero>,
         WriteStorage<'a, Position>,
         WriteStorage<'a, Velocity>,
+        WriteStorage<'a, RigidBody>,
+        ReadStorage<'a, ActiveEffects>,
+        ReadStorage<'a, Dying>,
     );
 
-    fn run(&mut self, (pressed_keys, mut hero, mut pos, mut vel): Self::SystemData) {
-        for (vel, pos, hero) in (&mut vel, &mut pos, &mut hero).join() {
-            vel.velocity.y = if pos.position.y >= 425.0 {
-                if pressed_keys
-                    .pressed_keys
-                    .contains(KeyboardKeys::KeyUp as u32)
-                {
-                    -400.0
-                } else {
-                    0.0
+    fn run(
+        &mut self,
+        (
+            pressed_keys,
+            physics,
+            bounds,
+            mut hero,
+            mut pos,
+            mut vel,
+            mut body,
+            effects,
+            dying,
+        ): Self::SystemData,
+    ) {
+        for (vel, pos, hero, body, effects, _) in (
+            &mut vel,
+            &mut pos,
+            &mut hero,
+            &mut body,
+            (&effects).maybe(),
+            !&dying,
+        )
+            .join()
+        {
+            let jump_pressed = pressed_keys
+                .pressed_keys
+                .contains(KeyboardKeys::KeyUp as u32);
+            let extra_jumps = effects
+                .and_then(|effects| effects.get(PowerUpKind::DoubleJump))
+                .map_or(0, |effect| effect.magnitude as i32);
+            let speed = 250.0 * effects.map_or(1.0, |e| e.multiplier(PowerUpKind::SpeedBoost));
+
+            if body.grounded {
+                hero.air_jumps = 0;
+                if jump_pressed {
+                    vel.velocity.y = -physics.jump_impulse;
+                    body.grounded = false;
                 }
-            } else if pos.position.y <= 300.0 {
-                200.0
-            } else {
-                vel.velocity.y
-            };
+            } else if jump_pressed && !hero.jump_pressed && hero.air_jumps < extra_jumps {
+                hero.air_jumps += 1;
+                vel.velocity.y = -physics.jump_impulse;
+            } else if !jump_pressed && hero.jump_pressed && vel.velocity.y < 0.0 {
+                vel.velocity.y *= physics.jump_cut;
+            }
+            hero.jump_pressed = jump_pressed;
 
             vel.velocity.x = if pressed_keys
                 .pressed_keys
@@ -167,7 +457,7 @@ impl<'a> System<'a> for HeroControlSystem {
                     .pressed_keys
                     .contains(KeyboardKeys::KeyLeft as u32)
             {
-                250.0
+                speed
             } else if !pressed_keys
                 .pressed_keys
                 .contains(KeyboardKeys::KeyRight as u32)
@@ -175,42 +465,115 @@ impl<'a> System<'a> for HeroControlSystem {
                     .pressed_keys
                     .contains(KeyboardKeys::KeyLeft as u32)
             {
-                -250.0
+                -speed
             } else {
                 0.0
             };
 
             if hero.reset_position {
-                pos.position = Vector::new(15.0, 300.0);
+                pos.position = Vector::new(
+                    bounds.left() + bounds.clamp_margin,
+                    bounds.top() + bounds.size.y / 2.0,
+                );
+                vel.velocity.y = 0.0;
+                body.grounded = false;
                 hero.reset_position = false;
             }
         }
     }
 }
 
+pub struct PhysicsSystem;
+
+impl<'a> System<'a> for PhysicsSystem {
+    type SystemData = (
+        Read<'a, PhysicsConfig>,
+        Read<'a, TileCollisionMap>,
+        ReadStorage<'a, Collider>,
+        WriteStorage<'a, Position>,
+        WriteStorage<'a, Velocity>,
+        WriteStorage<'a, RigidBody>,
+    );
+
+    fn run(&mut self, (physics, tile_map, collider, mut pos, mut vel, mut body): Self::SystemData) {
+        let time_step = 0.0167;
+
+        for (pos, vel, body, collider) in
+            (&mut pos, &mut vel, &mut body, (&collider).maybe()).join()
+        {
+            let bounds = collider.map_or(Rectangle::new(pos.position, Vector::ZERO), |collider| {
+                collider.bounds(pos.position)
+            });
+            let offset = bounds.center() - pos.position;
+            let half_size = bounds.size() / 2.0;
+
+            let mut center = pos.position + offset;
+            let on_tile = tile_map.resolve(&mut center, &mut vel.velocity, half_size, time_step);
+            pos.position = center - offset;
+            let floor = physics
+                .floor(&tile_map)
+                .filter(|floor| vel.velocity.y >= 0.0 && center.y + half_size.y >= *floor);
+            body.grounded = on_tile || floor.is_some();
+            if let Some(floor) = floor {
+                pos.position.y = floor - half_size.y - offset.y;
+                vel.velocity.y = 0.0;
+            } else {
+                vel.velocity.y += physics.gravity * body.gravity_scale * time_step;
+                if vel.velocity.y > physics.terminal_velocity {
+                    vel.velocity.y = physics.terminal_velocity;
+                }
+            }
+        }
+    }
+}
+
 pub struct OutOfBoundsSystem;
 
 impl<'a> System<'a> for OutOfBoundsSystem {
     type SystemData = (
         Entities<'a>,
-        ReadStorage<'a, Hero>,
-        ReadStorage<'a, CalculateOutOfBounds>,
+        Read<'a, WorldBounds>,
+        ReadStorage<'a, OutOfBounds>,
         WriteStorage<'a, Position>,
+        WriteStorage<'a, Lifetime>,
     );
 
-    fn run(&mut self, (entities, hero, oob, mut pos): Self::SystemData) {
-        for (_, pos, _, _) in (&entities, &mut pos, &oob, &hero).join() {
-            pos.position.x = if pos.position.x < 15.0 {
-                15.0
-            } else if pos.position.x > 785.0 {
-                785.0
-            } else {
-                pos.position.x
+    fn run(&mut self, (entities, bounds, oob, mut pos, mut lifetime): Self::SystemData) {
+        for (e, pos, oob) in (&entities, &mut pos, &oob).join() {
+            match oob.policy {
+                DespawnPolicy::Offscreen => {
+                    if bounds.is_outside(pos.position) {
+                        entities.delete(e).unwrap();
+                    }
+                }
+                DespawnPolicy::WrapAround => pos.position = bounds.wrap(pos.position),
+                DespawnPolicy::Clamp => pos.position.x = bounds.clamp_x(pos.position.x),
+                DespawnPolicy::AfterLifetime => {
+                    if bounds.is_outside(pos.position) && !lifetime.contains(e) {
+                        let remaining = Duration::from_millis(bounds.offscreen_lifetime_ms);
+                        lifetime.insert(e, Lifetime { remaining }).unwrap();
+                    }
+                }
+                DespawnPolicy::Never => {}
             }
         }
-        for (e, pos, _, _) in (&entities, &mut pos, &oob, !&hero).join() {
-            if pos.position.y > 700.0 || pos.position.x < -100.0 || pos.position.x > 900.0 {
-                entities.delete(e).unwrap();
+    }
+}
+
+pub struct LifetimeSystem;
+
+impl<'a> System<'a> for LifetimeSystem {
+    type SystemData = (Entities<'a>, WriteStorage<'a, Lifetime>);
+
+    fn run(&mut self, (entities, mut lifetime): Self::SystemData) {
+        let time_step = Duration::from_nanos(16700000);
+
+        for (e, lifetime) in (&entities, &mut lifetime).join() {
+            match lifetime.remaining.checked_sub(time_step) {
+                Some(remaining) if remaining > Duration::from_millis(0) => {
+                    lifetime.remaining = remaining
+                }
+                _ => entities.delete(e).unwrap(),
             }
         }
     }
@@ -218,231 +581,650 @@ impl<'a> System<'a> for OutOfBoundsSystem {
 
 pub struct CollisionSystem;
 
+/// Upper bound of overlap tests per pair for continuous collision.
+const MAX_SWEEP_STEPS: u32 = 32;
+
+/// Snapshot of one side of a potential contact, `previous` being its position last tick.
+struct CollisionBody<'b> {
+    entity: Entity,
+    previous: Vector,
+    position: Vector,
+    collider: &'b Collider,
+    layer: &'b CollisionLayer,
+}
+
 impl CollisionSystem {
-    fn hero_enemy_collision(
-        hero: &mut Hero,
-        enemy: &Enemy,
-        hero_render: &Render,
-        enemy_render: &Render,
-        hero_pos: Vector,
-        enemy_pos: Vector,
-        entities: &Entities,
+    fn matches(rule: &CollisionRule, first: &CollisionBody, second: &CollisionBody) -> bool {
+        if !first.layer.is_in(rule.first)
+            || !second.layer.is_in(rule.second)
+            || !first.layer.collides_with(second.layer)
+            || !second.layer.collides_with(first.layer)
+        {
+            return false;
+        }
+
+        // Fast bodies are tested at steps along their movement, each step shorter than half
+        // of the smallest hitbox so they can't tunnel through each other.
+        let first_motion = first.position - first.previous;
+        let second_motion = second.position - second.previous;
+        let step = first
+            .collider
+            .min_extent()
+            .min(second.collider.min_extent())
+            / 2.0;
+        let steps = if step > 0.0 && step.is_finite() {
+            ((first_motion - second_motion).len() / step).ceil() as u32
+        } else {
+            0
+        }
+        .max(1)
+        .min(MAX_SWEEP_STEPS);
+        (1..=steps).any(|i| {
+            let t = i as f32 / steps as f32;
+            let first_shapes = first
+                .collider
+                .shapes(rule.first_hitbox, first.previous + first_motion * t);
+            let second_shapes = second
+                .collider
+                .shapes(rule.second_hitbox, second.previous + second_motion * t);
+            first_shapes
+                .iter()
+                .any(|a| second_shapes.iter().any(|b| a.overlaps(b)))
+        })
+    }
+
+    fn previous_position(
+        continuous: &WriteStorage<ContinuousCollision>,
         e: Entity,
-    ) {
-        if hero_render.bounding_box.is_some() && enemy_render.bounding_box.is_some() {
-            let (hero_body_area, hero_feet_area) =
-                crate::hero::get_hero_body_feet_area(hero_render.bounding_box.unwrap(), hero_pos);
-            let (enemy_head_area, enemy_body_area) = crate::enemy::get_enemy_head_body_area(
-                enemy_render.bounding_box.unwrap(),
-                enemy_pos,
-            );
-
-            if enemy_head_area.overlaps(&hero_feet_area) {
-                hero.score += enemy.score;
-                entities.delete(e).unwrap();
-            } else if enemy_body_area.overlaps(&hero_body_area) && !hero.blinking {
-                hero.lives -= 1;
-                hero.blinking = true;
+        position: Vector,
+    ) -> Vector {
+        continuous
+            .get(e)
+            .and_then(|continuous| continuous.previous)
+            .unwrap_or(position)
+    }
+
+    /// First rule matching the pair in either order, as (first, second, rule index).
+    fn find_rule(
+        rules: &[CollisionRule],
+        a: &CollisionBody,
+        b: &CollisionBody,
+    ) -> Option<(Entity, Entity, usize)> {
+        rules.iter().enumerate().find_map(|(index, rule)| {
+            if CollisionSystem::matches(rule, a, b) {
+                Some((a.entity, b.entity, index))
+            } else if CollisionSystem::matches(rule, b, a) {
+                Some((b.entity, a.entity, index))
+            } else {
+                None
+            }
+        })
+    }
+
+    fn is_own_projectile(fireball: &ReadStorage<Fireball>, a: Entity, b: Entity) -> bool {
+        let owned_by = |projectile: Entity, owner: Entity| {
+            fireball
+                .get(projectile)
+                .map_or(false, |fireball| fireball.owner_id == Some(owner.id()))
+        };
+        owned_by(a, b) || owned_by(b, a)
+    }
+}
+
+/// Storages the collision actions act upon.
+struct CollisionTargets<'s, 'a: 's> {
+    flag: &'s mut GameStateFlagRes,
+    entities: &'s Entities<'a>,
+    hero: &'s mut WriteStorage<'a, Hero>,
+    active_effects: &'s mut WriteStorage<'a, ActiveEffects>,
+    boss: &'s mut WriteStorage<'a, Boss>,
+    change_sprite: &'s mut WriteStorage<'a, ChangeSprite>,
+    shooter: &'s mut WriteStorage<'a, Shooter>,
+    pos: &'s mut WriteStorage<'a, Position>,
+    vel: &'s mut WriteStorage<'a, Velocity>,
+    enemy: &'s ReadStorage<'a, Enemy>,
+    healing: &'s ReadStorage<'a, Healing>,
+    power_up: &'s ReadStorage<'a, PowerUp>,
+    collider: &'s ReadStorage<'a, Collider>,
+    fireball: &'s ReadStorage<'a, Fireball>,
+    particles: &'s mut ParticleEffects,
+    camera_effects: &'s mut CameraEffects,
+    stats: &'s mut SessionStats,
+    animation: &'s ReadStorage<'a, Animation>,
+    dying: &'s mut WriteStorage<'a, Dying>,
+    hurt: &'s mut WriteStorage<'a, Hurt>,
+    destroyed: HashSet<Entity>,
+}
+
+impl<'s, 'a> CollisionTargets<'s, 'a> {
+    /// Whether `e` was deleted this tick or is playing its death clip.
+    fn is_destroyed(&self, e: Entity) -> bool {
+        self.destroyed.contains(&e) || self.dying.contains(e)
+    }
+
+    fn clip_duration(&self, e: Entity, name: AnimationClipName) -> Option<Duration> {
+        self.animation
+            .get(e)
+            .and_then(|animation| animation.clips.get(&name))
+            .map(AnimationClip::duration)
+            .filter(|duration| *duration > Duration::from_millis(0))
+    }
+
+    /// Plays the death clip before deleting `e` and raising `flag`, or does both right away
+    /// when it has none.
+    fn kill(&mut self, e: Entity, flag: Option<GameStateFlag>) -> bool {
+        match self.clip_duration(e, AnimationClipName::Death) {
+            Some(remaining) => {
+                self.shooter.remove(e);
+                if let Some(vel) = self.vel.get_mut(e) {
+                    vel.velocity = Vector::ZERO;
+                }
+                self.dying.insert(e, Dying { remaining, flag }).unwrap();
+                true
+            }
+            None => {
+                if flag.is_some() {
+                    self.flag.flag = flag;
+                }
+                self.destroy(e)
             }
         }
     }
 
-    fn hero_healing_collision(
-        hero: &mut Hero,
-        healing: &Healing,
-        hero_render: &Render,
-        healing_render: &Render,
-        hero_pos: Vector,
-        healing_pos: Vector,
-        entities: &Entities,
-        e: Entity,
-    ) {
-        if hero_render.bounding_box.is_some() && healing_render.bounding_box.is_some() {
-            let hero_bounding_box = hero_render.bounding_box.unwrap().with_center(hero_pos);
-            let healing_bounding_box = healing_render
-                .bounding_box
-                .unwrap()
-                .with_center(healing_pos);
-
-            if hero_bounding_box.overlaps(&healing_bounding_box) {
-                hero.lives += 1;
-                hero.score += healing.score;
-                entities.delete(e).unwrap();
+    fn hurt(&mut self, e: Entity) {
+        if let Some(remaining) = self.clip_duration(e, AnimationClipName::Hurt) {
+            self.hurt.insert(e, Hurt { remaining }).unwrap();
+        }
+    }
+
+    /// What `e` is, for the session stats.
+    fn kind_name(&self, e: Entity) -> String {
+        if self.boss.contains(e) {
+            "Boss".to_string()
+        } else if self.fireball.contains(e) {
+            "Fireball".to_string()
+        } else if let Some(enemy) = self.enemy.get(e) {
+            enemy
+                .kind
+                .map_or("Enemy".to_string(), |kind| format!("{:?}", kind))
+        } else {
+            "Unknown".to_string()
+        }
+    }
+
+    fn position(&self, e: Entity) -> Option<Vector> {
+        self.pos.get(e).map(|pos| pos.position)
+    }
+
+    fn defeat_enemy(&mut self, e: Entity) {
+        if !self.is_destroyed(e) && self.enemy.contains(e) {
+            let (name, position) = (self.kind_name(e), self.position(e));
+            self.stats.enemy_defeated(&name, position);
+        }
+    }
+
+    /// Applies the actions in order, stopping at the first one without effect.
+    fn apply(&mut self, actions: &[CollisionAction], first: Entity, second: Entity) {
+        let target = |target| match target {
+            CollisionTarget::First => (first, second),
+            CollisionTarget::Second => (second, first),
+        };
+        for action in actions {
+            let applied = match *action {
+                CollisionAction::Damage(t) => {
+                    let (e, source) = target(t);
+                    self.damage(e, source)
+                }
+                CollisionAction::Stomp => self.stomp(first, second),
+                CollisionAction::Pickup => self.pickup(first, second),
+                CollisionAction::Bounce(t) => self.bounce(target(t).0),
+                CollisionAction::Destroy(t) => self.destroy(target(t).0),
+                CollisionAction::Block(t) => {
+                    let (e, other) = target(t);
+                    self.block(e, other)
+                }
+                CollisionAction::Emit(t, effect) => self.emit(target(t).0, effect),
+            };
+            if !applied {
+                break;
             }
         }
     }
 
-    fn hero_fireball_collision(
-        hero: &mut Hero,
-        hero_render: &Render,
-        fireball_render: &Render,
-        hero_pos: Vector,
-        fireball_pos: Vector,
-        entities: &Entities,
-        e: Entity,
-    ) {
-        if hero_render.bounding_box.is_some() && fireball_render.bounding_box.is_some() {
-            let hero_bounding_box = hero_render.bounding_box.unwrap().with_center(hero_pos);
-            let fireball_bounding_box = fireball_render
-                .bounding_box
-                .unwrap()
-                .with_center(fireball_pos);
-
-            if hero_bounding_box.overlaps(&fireball_bounding_box) && !hero.blinking {
-                hero.blinking = true;
+    fn emit(&mut self, e: Entity, effect: ParticleEffectName) -> bool {
+        if let Some(pos) = self.pos.get(e) {
+            self.particles.emit(effect, pos.position);
+        }
+        true
+    }
+
+    fn destroy(&mut self, e: Entity) -> bool {
+        if self.destroyed.insert(e) {
+            self.entities.delete(e).unwrap();
+        }
+        true
+    }
+
+    fn add_score(&mut self, e: Entity, score: i32) {
+        if let Some(hero) = self.hero.get_mut(e) {
+            let multiplier = self.active_effects.get(e).map_or(1.0, |effects| {
+                effects.multiplier(PowerUpKind::ScoreMultiplier)
+            });
+            hero.score += (score as f32 * multiplier) as i32;
+        }
+    }
+
+    fn damage(&mut self, e: Entity, source: Entity) -> bool {
+        if let Some(hero) = self.hero.get_mut(e) {
+            if hero.blinking || hero.invincible {
+                return false;
+            }
+            let shielded = self
+                .active_effects
+                .get_mut(e)
+                .map_or(false, |effects| effects.remove(PowerUpKind::Shield));
+            if !shielded {
                 hero.lives -= 1;
-                entities.delete(e).unwrap();
             }
+            hero.blinking = true;
+            let lives = hero.lives;
+            self.camera_effects.trigger(CameraEvent::HeroHit);
+            let (source, position) = (self.kind_name(source), self.position(e));
+            if shielded {
+                self.stats.shield_absorbed(&source, position);
+            } else {
+                self.stats.hero_damaged(&source, lives, position);
+            }
+            if lives > 0 {
+                self.hurt(e);
+            }
+            true
+        } else if self.boss.contains(e) {
+            self.damage_boss(e);
+            true
+        } else {
+            self.defeat_enemy(e);
+            self.kill(e, None)
         }
     }
 
-    fn hero_boss_collision<'a>(
-        flag: &mut Write<'a, GameStateFlagRes>,
-        hero: &mut Hero,
-        enemy: &Enemy,
-        boss: &mut Boss,
-        hero_render: &Render,
-        enemy_render: &Render,
-        hero_pos: Vector,
-        enemy_pos: Vector,
-        entities: &Entities,
-        e: Entity,
-        change_sprite: Option<&mut ChangeSprite>,
-        shooter: Option<&mut Shooter>,
-    ) {
-        if hero_render.bounding_box.is_some() && enemy_render.bounding_box.is_some() {
-            let (hero_body_area, hero_feet_area) =
-                crate::hero::get_hero_body_feet_area(hero_render.bounding_box.unwrap(), hero_pos);
-            let (enemy_head_area, enemy_body_area) = crate::enemy::get_enemy_head_body_area(
-                enemy_render.bounding_box.unwrap(),
-                enemy_pos,
-            );
-
-            if enemy_head_area.overlaps(&hero_feet_area) {
-                hero.score += enemy.score;
-                hero.reset_position = true;
-                hero.blinking = true;
+    fn damage_boss(&mut self, e: Entity) {
+        let (lives, normal_lives) = match self.boss.get_mut(e) {
+            Some(boss) => {
                 boss.lives -= 1;
                 boss.normal_lives -= 1;
-                if boss.lives == 0 {
-                    flag.flag = Some(GameStateFlag::Victory);
-                    entities.delete(e).unwrap();
-                } else if boss.normal_lives == 0 {
-                    if let Some(change_sprite) = change_sprite {
-                        change_sprite.do_change = true;
-                    }
-                    if let Some(shooter) = shooter {
-                        shooter.maximum_fireballs = 4;
-                    }
-                }
-            } else if enemy_body_area.overlaps(&hero_body_area) && !hero.blinking {
-                hero.lives -= 1;
+                (boss.lives, boss.normal_lives)
+            }
+            None => return,
+        };
+        if lives == 0 {
+            self.camera_effects.trigger(CameraEvent::BossDefeated);
+            self.defeat_enemy(e);
+            self.kill(e, Some(GameStateFlag::Victory));
+            return;
+        }
+        self.hurt(e);
+        if normal_lives == 0 {
+            self.camera_effects.trigger(CameraEvent::BossAngry);
+            if let Some(change_sprite) = self.change_sprite.get_mut(e) {
+                change_sprite.do_change = true;
+            }
+            if let Some(shooter) = self.shooter.get_mut(e) {
+                shooter.maximum_fireballs = 4;
+            }
+        } else {
+            self.camera_effects.trigger(CameraEvent::BossHit);
+        }
+    }
+
+    fn stomp(&mut self, first: Entity, second: Entity) -> bool {
+        let score = self.enemy.get(second).map_or(0, |enemy| enemy.score);
+        self.add_score(first, score);
+        if self.boss.contains(second) {
+            if let Some(hero) = self.hero.get_mut(first) {
+                hero.reset_position = true;
                 hero.blinking = true;
             }
+            self.damage_boss(second);
+            true
+        } else {
+            self.defeat_enemy(second);
+            self.kill(second, None)
         }
     }
+
+    fn pickup(&mut self, first: Entity, second: Entity) -> bool {
+        let (healing, power_up) = (self.healing, self.power_up);
+        if let Some(healing) = healing.get(second) {
+            if let Some(hero) = self.hero.get_mut(first) {
+                hero.lives += 1;
+            }
+            self.add_score(first, healing.score);
+            let position = self.position(first);
+            self.stats.potion_used(position);
+        } else if let Some(power_up) = power_up.get(second) {
+            if let Some(effects) = self.active_effects.get_mut(first) {
+                effects.apply(power_up);
+            }
+            self.add_score(first, power_up.score);
+            let position = self.position(first);
+            self.stats.power_up_collected(power_up.kind, position);
+        } else {
+            return false;
+        }
+        self.destroy(second)
+    }
+
+    fn bounce(&mut self, e: Entity) -> bool {
+        match self.vel.get_mut(e) {
+            Some(vel) => {
+                vel.velocity = vel.velocity * -1.0;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Pushes `e` out of `other` along the axis of least penetration.
+    fn block(&mut self, e: Entity, other: Entity) -> bool {
+        let bounds = |e: Entity| match (self.pos.get(e), self.collider.get(e)) {
+            (Some(pos), Some(collider)) => Some(collider.bounds(pos.position)),
+            _ => None,
+        };
+        let (area, other_area) = match (bounds(e), bounds(other)) {
+            (Some(area), Some(other_area)) => (area, other_area),
+            _ => return false,
+        };
+        let pushes = [
+            Vector::new(other_area.pos.x - (area.pos.x + area.size.x), 0.0),
+            Vector::new(other_area.pos.x + other_area.size.x - area.pos.x, 0.0),
+            Vector::new(0.0, other_area.pos.y - (area.pos.y + area.size.y)),
+            Vector::new(0.0, other_area.pos.y + other_area.size.y - area.pos.y),
+        ];
+        let push = pushes
+            .iter()
+            .cloned()
+            .min_by(|a, b| a.len2().partial_cmp(&b.len2()).unwrap())
+            .unwrap();
+        if let Some(pos) = self.pos.get_mut(e) {
+            pos.position += push;
+        }
+        if let Some(vel) = self.vel.get_mut(e) {
+            if push.x != 0.0 {
+                vel.velocity.x = 0.0;
+            } else {
+                vel.velocity.y = 0.0;
+            }
+        }
+        true
+    }
 }
 
 impl<'a> System<'a> for CollisionSystem {
     type SystemData = (
         Write<'a, GameStateFlagRes>,
+        Read<'a, SpatialGrid>,
+        Read<'a, CollisionRules>,
+        Write<'a, ParticleEffects>,
+        Write<'a, CameraEffects>,
+        Write<'a, SessionStats>,
         Entities<'a>,
         WriteStorage<'a, Hero>,
         ReadStorage<'a, Enemy>,
         WriteStorage<'a, Boss>,
         ReadStorage<'a, Healing>,
-        ReadStorage<'a, Position>,
-        ReadStorage<'a, Render>,
+        WriteStorage<'a, Position>,
+        WriteStorage<'a, Velocity>,
+        ReadStorage<'a, Collider>,
+        ReadStorage<'a, CollisionLayer>,
+        WriteStorage<'a, ContinuousCollision>,
         WriteStorage<'a, ChangeSprite>,
         WriteStorage<'a, Shooter>,
         ReadStorage<'a, Fireball>,
+        ReadStorage<'a, PowerUp>,
+        WriteStorage<'a, ActiveEffects>,
+        ReadStorage<'a, Animation>,
+        WriteStorage<'a, Dying>,
+        WriteStorage<'a, Hurt>,
+        ReadStorage<'a, MapTile>,
     );
 
     fn run(
         &mut self,
         (
             mut flag,
+            grid,
+            rules,
+            mut particles,
+            mut camera_effects,
+            mut stats,
             entities,
             mut hero,
             enemy,
             mut boss,
             healing,
-            pos,
-            render,
+            mut pos,
+            mut vel,
+            collider,
+            layer,
+            mut continuous,
             mut change_sprite,
             mut shooter,
             fireball,
+            power_up,
+            mut active_effects,
+            animation,
+            mut dying,
+            mut hurt,
+            map_tile,
         ): Self::SystemData,
     ) {
-        for (e_hero, hero, hero_pos, hero_render) in (&entities, &mut hero, &pos, &render).join() {
-            for (e, enemy_pos, enemy_render, enemy) in (&entities, &pos, &render, &enemy).join() {
-                let boss: Option<&mut Boss> = boss.get_mut(e);
-                match boss {
-                    Some(boss) => {
-                        let change_sprite: Option<&mut ChangeSprite> = change_sprite.get_mut(e);
-                        let shooter: Option<&mut Shooter> = shooter.get_mut(e);
-                        CollisionSystem::hero_boss_collision(
-                            &mut flag,
-                            hero,
-                            enemy,
-                            boss,
-                            hero_render,
-                            enemy_render,
-                            hero_pos.position,
-                            enemy_pos.position,
-                            &entities,
-                            e,
-                            change_sprite,
-                            shooter,
-                        );
-                    }
-                    None => {
-                        CollisionSystem::hero_enemy_collision(
-                            hero,
-                            enemy,
-                            hero_render,
-                            enemy_render,
-                            hero_pos.position,
-                            enemy_pos.position,
-                            &entities,
-                            e,
-                        );
-                    }
+        let mut contacts = Vec::new();
+        // Map tiles never move, so they are only found by the bodies querying around them.
+        for (a, a_pos, a_collider, a_layer, _) in
+            (&entities, &pos, &collider, &layer, !&map_tile).join()
+        {
+            let a_body = CollisionBody {
+                entity: a,
+                previous: CollisionSystem::previous_position(&continuous, a, a_pos.position),
+                position: a_pos.position,
+                collider: a_collider,
+                layer: a_layer,
+            };
+            for b in grid.query(a_collider.swept_bounds(a_body.previous, a_body.position)) {
+                if (b.id() <= a.id() && !map_tile.contains(b))
+                    || CollisionSystem::is_own_projectile(&fireball, a, b)
+                {
+                    continue;
+                }
+                let b_body = match (pos.get(b), collider.get(b), layer.get(b)) {
+                    (Some(b_pos), Some(b_collider), Some(b_layer)) => CollisionBody {
+                        entity: b,
+                        previous: CollisionSystem::previous_position(
+                            &continuous,
+                            b,
+                            b_pos.position,
+                        ),
+                        position: b_pos.position,
+                        collider: b_collider,
+                        layer: b_layer,
+                    },
+                    _ => continue,
                 };
+                if let Some(contact) = CollisionSystem::find_rule(&rules.rules, &a_body, &b_body) {
+                    contacts.push(contact);
+                }
             }
+        }
 
-            for (e, healing_pos, healing_render, healing) in
-                (&entities, &pos, &render, &healing).join()
-            {
-                CollisionSystem::hero_healing_collision(
-                    hero,
-                    healing,
-                    hero_render,
-                    healing_render,
-                    hero_pos.position,
-                    healing_pos.position,
-                    &entities,
-                    e,
-                );
+        for (pos, continuous) in (&pos, &mut continuous).join() {
+            continuous.previous = Some(pos.position);
+        }
+
+        let mut targets = CollisionTargets {
+            flag: &mut flag,
+            entities: &entities,
+            hero: &mut hero,
+            active_effects: &mut active_effects,
+            boss: &mut boss,
+            change_sprite: &mut change_sprite,
+            shooter: &mut shooter,
+            pos: &mut pos,
+            vel: &mut vel,
+            enemy: &enemy,
+            healing: &healing,
+            power_up: &power_up,
+            collider: &collider,
+            fireball: &fireball,
+            particles: &mut particles,
+            camera_effects: &mut camera_effects,
+            stats: &mut stats,
+            animation: &animation,
+            dying: &mut dying,
+            hurt: &mut hurt,
+            destroyed: HashSet::new(),
+        };
+        for (first, second, index) in contacts {
+            if targets.is_destroyed(first) || targets.is_destroyed(second) {
+                continue;
+            }
+            targets.apply(&rules.rules[index].actions, first, second);
+        }
+
+        let defeated = (&entities, &*targets.hero)
+            .join()
+            .filter(|(_, hero)| hero.lives <= 0)
+            .map(|(e, _)| e)
+            .collect::<Vec<_>>();
+        for e_hero in defeated {
+            if !targets.is_destroyed(e_hero) {
+                targets.camera_effects.trigger(CameraEvent::HeroDefeated);
+                targets.kill(e_hero, Some(GameStateFlag::Defeat));
             }
+        }
+    }
+}
 
-            for (e, fireball_pos, fireball_render, _) in
-                (&entities, &pos, &render, &fireball).join()
-            {
-                CollisionSystem::hero_fireball_collision(
-                    hero,
-                    hero_render,
-                    fireball_render,
-                    hero_pos.position,
-                    fireball_pos.position,
-                    &entities,
-                    e,
-                );
+/// Picks the clip matching each entity's state.
+pub struct AnimationStateSystem;
+
+impl<'a> System<'a> for AnimationStateSystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, Dying>,
+        ReadStorage<'a, Hurt>,
+        ReadStorage<'a, Velocity>,
+        ReadStorage<'a, RigidBody>,
+        WriteStorage<'a, Animation>,
+    );
+
+    fn run(&mut self, (entities, dying, hurt, vel, body, mut animation): Self::SystemData) {
+        for (e, animation, vel, body) in
+            (&entities, &mut animation, vel.maybe(), body.maybe()).join()
+        {
+            let clip = if dying.contains(e) {
+                AnimationClipName::Death
+            } else if hurt.contains(e) {
+                AnimationClipName::Hurt
+            } else if body.map_or(false, |body| !body.grounded) {
+                AnimationClipName::Jump
+            } else if vel.map_or(false, |vel| vel.velocity.x != 0.0) {
+                AnimationClipName::Walk
+            } else {
+                AnimationClipName::Idle
+            };
+            animation.play(clip);
+        }
+    }
+}
+
+/// Flips sprites with a `facing` towards their horizontal velocity.
+pub struct FacingSystem;
+
+impl<'a> System<'a> for FacingSystem {
+    type SystemData = (ReadStorage<'a, Velocity>, WriteStorage<'a, SpriteTransform>);
+
+    fn run(&mut self, (vel, mut transform): Self::SystemData) {
+        for (vel, transform) in (&vel, &mut transform).join() {
+            if let Some(facing) = transform.facing {
+                if vel.velocity.x != 0.0 {
+                    transform.flip_x = (vel.velocity.x > 0.0) == (facing == Facing::Left);
+                }
+            }
+        }
+    }
+}
+
+pub struct AnimationSystem;
+
+impl<'a> System<'a> for AnimationSystem {
+    type SystemData = (
+        Write<'a, SpriteTable>,
+        WriteStorage<'a, Animation>,
+        WriteStorage<'a, Render>,
+    );
+
+    fn run(&mut self, (mut sprites, mut animation, mut render): Self::SystemData) {
+        let time_step = Duration::from_nanos(16700000);
+
+        for (animation, render) in (&mut animation, &mut render).join() {
+            animation.resolve(&mut sprites);
+            animation.advance(time_step);
+            if let Some(sprite) = animation.sprite() {
+                render.sprite = sprite;
             }
+        }
+    }
+}
+
+/// Counts the hurt clips down.
+pub struct HurtSystem;
+
+impl<'a> System<'a> for HurtSystem {
+    type SystemData = (Entities<'a>, WriteStorage<'a, Hurt>);
+
+    fn run(&mut self, (entities, mut hurt): Self::SystemData) {
+        let time_step = Duration::from_nanos(16700000);
+
+        let mut healed = Vec::new();
+        for (e, hurt) in (&entities, &mut hurt).join() {
+            match hurt.remaining.checked_sub(time_step) {
+                Some(remaining) if remaining > Duration::from_millis(0) => {
+                    hurt.remaining = remaining
+                }
+                _ => healed.push(e),
+            }
+        }
+        for e in healed {
+            hurt.remove(e);
+        }
+    }
+}
+
+/// Deletes dying entities once their death clip is over, raising their game state flag.
+pub struct DyingSystem;
+
+impl<'a> System<'a> for DyingSystem {
+    type SystemData = (
+        Entities<'a>,
+        Write<'a, GameStateFlagRes>,
+        WriteStorage<'a, Dying>,
+    );
 
-            if hero.lives == 0 {
-                flag.flag = Some(GameStateFlag::Defeat);
-                entities.delete(e_hero).unwrap();
+    fn run(&mut self, (entities, mut flag, mut dying): Self::SystemData) {
+        let time_step = Duration::from_nanos(16700000);
+
+        for (e, dying) in (&entities, &mut dying).join() {
+            match dying.remaining.checked_sub(time_step) {
+                Some(remaining) if remaining > Duration::from_millis(0) => {
+                    dying.remaining = remaining
+                }
+                _ => {
+                    if dying.flag.is_some() {
+                        flag.flag = dying.flag;
+                    }
+                    entities.delete(e).unwrap();
+                }
             }
         }
     }
@@ -483,16 +1265,32 @@ impl<'a> System<'a> for FireballSystem {
         WriteStorage<'a, Position>,
         WriteStorage<'a, Shooter>,
         ReadStorage<'a, Fireball>,
+        Read<'a, TweenEvents>,
+        Write<'a, SpriteTable>,
         Read<'a, LazyUpdate>,
     );
 
-    fn run(&mut self, (entities, mut pos, mut shooter, fireball, lazy): Self::SystemData) {
+    fn run(
+        &mut self,
+        (entities, mut pos, mut shooter, fireball, tween_events, mut sprites, lazy): Self::SystemData,
+    ) {
+        for e in &tween_events.finished {
+            if let Some(shooter) = shooter.get_mut(*e) {
+                shooter.active = true;
+            }
+        }
+
+        let mut fireballs_per_owner: HashMap<Index, i32> = HashMap::new();
+        for fireball in (&fireball).join() {
+            if let Some(owner_id) = fireball.owner_id {
+                *fireballs_per_owner.entry(owner_id).or_insert(0) += 1;
+            }
+        }
+
         for (e, pos, shooter) in (&entities, &mut pos, &mut shooter).join() {
-            shooter.fireball_amount = 0;
-            for fireball in (&fireball).join() {
-                if fireball.owner_id.is_some() && fireball.owner_id.unwrap() == e.id() {
-                    shooter.fireball_amount += 1;
-                }
+            shooter.fireball_amount = fireballs_per_owner.get(&e.id()).cloned().unwrap_or(0);
+            if !shooter.active {
+                continue;
             }
 
             while shooter.fireball_amount < shooter.maximum_fireballs {
@@ -501,6 +1299,12 @@ impl<'a> System<'a> for FireballSystem {
                 let fireball_config = FireballConfig {
                     sprite: shooter.projectile_sprite.clone(),
                     position: pos.position,
+                    collider: shooter.projectile_collider.clone(),
+                    collision_layer: shooter.projectile_layer.clone(),
+                    fast: shooter.fast_projectiles,
+                    trail: shooter.projectile_trail.clone(),
+                    layer: shooter.projectile_render_layer,
+                    z: 0.0,
                     velocity: Vector::new(
                         -1000.0
                             * ((shooter.coefficient.0 * (shooter.fireball_amount + 1) as f32
@@ -511,6 +1315,7 @@ impl<'a> System<'a> for FireballSystem {
                 };
                 crate::enemy::create_fireball(
                     lazy.create_entity(&entities),
+                    &mut sprites,
                     Some(e.id()),
                     fireball_config,
                 );
@@ -520,3 +1325,260 @@ impl<'a> System<'a> for FireballSystem {
         }
     }
 }
+
+pub struct ActiveEffectsSystem;
+
+impl<'a> System<'a> for ActiveEffectsSystem {
+    type SystemData = WriteStorage<'a, ActiveEffects>;
+
+    fn run(&mut self, mut active_effects: Self::SystemData) {
+        let time_step = Duration::from_nanos(16700000);
+
+        for active_effects in (&mut active_effects).join() {
+            for effect in active_effects.effects.iter_mut() {
+                effect.remaining = effect
+                    .remaining
+                    .checked_sub(time_step)
+                    .unwrap_or_else(|| Duration::from_millis(0));
+            }
+            active_effects
+                .effects
+                .retain(|effect| effect.remaining > Duration::from_millis(0));
+        }
+    }
+}
+
+/// Starts the effects triggered this tick, runs the emitters and ages their particles.
+pub struct ParticleSystem;
+
+impl<'a> System<'a> for ParticleSystem {
+    type SystemData = (
+        Entities<'a>,
+        Write<'a, ParticleEffects>,
+        Write<'a, SpriteTable>,
+        ReadStorage<'a, Position>,
+        WriteStorage<'a, Velocity>,
+        WriteStorage<'a, ParticleEmitter>,
+        WriteStorage<'a, Particle>,
+        WriteStorage<'a, SpriteTransform>,
+        Read<'a, LazyUpdate>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            mut effects,
+            mut sprites,
+            pos,
+            mut vel,
+            mut emitter,
+            mut particle,
+            mut transform,
+            lazy,
+        ): Self::SystemData,
+    ) {
+        let time_step = Duration::from_nanos(16700000);
+        let mut rng = thread_rng();
+
+        let pending = std::mem::replace(&mut effects.pending, Vec::new());
+        for (effect, position) in pending {
+            if let Some(config) = effects.effects.get(&effect) {
+                let mut emitter = ParticleEmitter::new(config.clone());
+                emitter.despawn = true;
+                lazy.create_entity(&entities)
+                    .with(Position { position })
+                    .with(emitter)
+                    .build();
+            }
+        }
+
+        for (e, pos, emitter) in (&entities, &pos, &mut emitter).join() {
+            match emitter.advance(time_step) {
+                Some(count) => {
+                    let sprite = match emitter.sprite {
+                        Some(sprite) => sprite,
+                        None => {
+                            let sprite = sprites.intern(&emitter.config.sprite);
+                            emitter.sprite = Some(sprite);
+                            sprite
+                        }
+                    };
+                    for _ in 0..count {
+                        create_particle(
+                            lazy.create_entity(&entities),
+                            &emitter.config,
+                            sprite,
+                            pos.position,
+                            &mut rng,
+                        );
+                    }
+                }
+                None if emitter.despawn => entities.delete(e).unwrap(),
+                None => lazy.remove::<ParticleEmitter>(e),
+            }
+        }
+
+        for (e, particle, vel, transform) in
+            (&entities, &mut particle, &mut vel, &mut transform).join()
+        {
+            particle.age += time_step;
+            if particle.age >= particle.lifetime {
+                entities.delete(e).unwrap();
+                continue;
+            }
+            vel.velocity.y += particle.gravity * 0.0167;
+            particle.over_life.apply(particle.progress(), transform);
+        }
+    }
+}
+
+/// Plays tweens, after movement so `Move` steps add to the velocity.
+pub struct TweenSystem;
+
+impl<'a> System<'a> for TweenSystem {
+    type SystemData = (
+        Entities<'a>,
+        Write<'a, TweenEvents>,
+        WriteStorage<'a, Tween>,
+        WriteStorage<'a, Position>,
+        WriteStorage<'a, SpriteTransform>,
+        Read<'a, LazyUpdate>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, mut events, mut tween, mut pos, mut transform, lazy): Self::SystemData,
+    ) {
+        let time_step = Duration::from_nanos(16700000);
+        events.finished.clear();
+
+        for (e, tween, pos, transform) in (
+            &entities,
+            &mut tween,
+            (&mut pos).maybe(),
+            (&mut transform).maybe(),
+        )
+            .join()
+        {
+            if tween.finished {
+                continue;
+            }
+            let moved = tween.advance(time_step);
+            let value = tween.value();
+            if let Some(pos) = pos {
+                pos.position += moved;
+                if let Some(TweenValue::Position(position)) = value {
+                    pos.position = position;
+                }
+            }
+            if let Some(transform) = transform {
+                match value {
+                    Some(TweenValue::Offset(offset)) => transform.offset = offset,
+                    Some(TweenValue::Scale(scale)) => transform.scale = Vector::new(scale, scale),
+                    Some(TweenValue::Rotation(rotation)) => transform.rotation = rotation,
+                    Some(TweenValue::Alpha(alpha)) => transform.alpha = alpha,
+                    _ => {}
+                }
+            }
+
+            if tween.finished {
+                events.finished.push(e);
+                match tween.config.on_complete {
+                    TweenCompletion::Remove => lazy.remove::<Tween>(e),
+                    TweenCompletion::Delete => entities.delete(e).unwrap(),
+                    TweenCompletion::Keep => {}
+                }
+            }
+        }
+    }
+}
+
+/// Decays the screen shake and flash, also while the game is over.
+pub struct CameraEffectsSystem;
+
+impl<'a> System<'a> for CameraEffectsSystem {
+    type SystemData = Write<'a, CameraEffects>;
+
+    fn run(&mut self, mut effects: Self::SystemData) {
+        let time_step = Duration::from_nanos(16700000);
+        let mut rng = thread_rng();
+        effects.update(
+            time_step,
+            (rng.gen_range(-1.0, 1.0), rng.gen_range(-1.0, 1.0)),
+        );
+    }
+}
+
+pub struct CameraSystem;
+
+impl<'a> System<'a> for CameraSystem {
+    type SystemData = (
+        Write<'a, Camera>,
+        Read<'a, WorldBounds>,
+        ReadStorage<'a, Position>,
+    );
+
+    fn run(&mut self, (mut camera, bounds, pos): Self::SystemData) {
+        match camera.target.and_then(|target| pos.get(target)) {
+            Some(target) => camera.update(target.position, &bounds),
+            None => camera.clamp(&bounds),
+        }
+    }
+}
+
+pub struct SpatialGridSystem;
+
+impl<'a> System<'a> for SpatialGridSystem {
+    type SystemData = (
+        Write<'a, SpatialGrid>,
+        Entities<'a>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Collider>,
+        ReadStorage<'a, ContinuousCollision>,
+    );
+
+    fn run(&mut self, (mut grid, entities, pos, collider, continuous): Self::SystemData) {
+        grid.clear();
+        for (e, pos, collider, continuous) in
+            (&entities, &pos, &collider, (&continuous).maybe()).join()
+        {
+            let previous = continuous
+                .and_then(|continuous| continuous.previous)
+                .unwrap_or(pos.position);
+            grid.insert(e, collider.swept_bounds(previous, pos.position));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn draws_are_grouped_by_sprite_only_on_batched_layers() {
+        let (a, b) = (Some(SpriteHandle(1)), Some(SpriteHandle(0)));
+        let mut draws = vec![
+            (1, 0.0, a, "batched a"),
+            (0, 0.0, None, "unbatched 1"),
+            (1, 0.0, b, "batched b"),
+            (0, 0.0, None, "unbatched 2"),
+            (1, 0.0, a, "batched a again"),
+            (1, -1.0, a, "batched behind"),
+            (1, 0.0, None, "label"),
+        ];
+        sort_draws(&mut draws);
+        assert_eq!(
+            draws.iter().map(|draw| draw.3).collect::<Vec<_>>(),
+            vec![
+                "unbatched 1",
+                "unbatched 2",
+                "batched behind",
+                "label",
+                "batched b",
+                "batched a",
+                "batched a again",
+            ]
+        );
+    }
+}
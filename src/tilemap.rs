@@ -0,0 +1,477 @@
+use crate::{
+    bounds::WorldBounds,
+    collision::{CollisionLayerConfig, CollisionLayerName},
+    component::{Collider, CollisionLayer, MapTile, Position, Render},
+    enemy::EnemyType,
+    entity_factory::PrefabsConfig,
+    healing::HealingConfig,
+    resources::RenderLayer,
+    sprite::intern_sprite,
+};
+use quicksilver::geom::{Rectangle, Shape, Vector};
+use serde::{Deserialize, Serialize};
+use specs::{Builder, Entity, World};
+
+const FLIPPED_FLAGS_MASK: u32 = 0x1FFF_FFFF;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct TileMapConfig {
+    pub path: String,
+    pub collision_layer: String,
+    /// Collision layer of the solid tiles, which projectiles hit by default.
+    pub wall_layer: CollisionLayerConfig,
+}
+
+impl Default for TileMapConfig {
+    fn default() -> TileMapConfig {
+        TileMapConfig {
+            path: "maps/level1.json".to_string(),
+            collision_layer: "collision".to_string(),
+            wall_layer: CollisionLayerConfig::new(
+                &[CollisionLayerName::Wall],
+                &[CollisionLayerName::Projectile],
+            ),
+        }
+    }
+}
+
+/// Subset of Tiled's JSON map format used by the engine.
+#[derive(Deserialize, Debug, Clone)]
+pub struct TiledMap {
+    pub width: u32,
+    pub height: u32,
+    pub tilewidth: u32,
+    pub tileheight: u32,
+    pub layers: Vec<TiledLayer>,
+    #[serde(default)]
+    pub tilesets: Vec<TiledTileset>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum TiledLayer {
+    TileLayer {
+        name: String,
+        width: u32,
+        height: u32,
+        data: Vec<u32>,
+        #[serde(default = "default_visible")]
+        visible: bool,
+    },
+    ObjectGroup {
+        name: String,
+        objects: Vec<TiledObject>,
+    },
+    #[serde(other)]
+    Other,
+}
+
+fn default_visible() -> bool {
+    true
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct TiledTileset {
+    pub firstgid: u32,
+    #[serde(default)]
+    pub name: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct TiledObject {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default, rename = "type", alias = "class")]
+    pub object_type: String,
+    pub x: f32,
+    pub y: f32,
+    #[serde(default)]
+    pub width: f32,
+    #[serde(default)]
+    pub height: f32,
+}
+
+impl TiledObject {
+    pub fn center(&self) -> Vector {
+        Vector::new(self.x + self.width / 2.0, self.y + self.height / 2.0)
+    }
+}
+
+impl TiledMap {
+    pub fn tile_size(&self) -> Vector {
+        Vector::new(self.tilewidth as f32, self.tileheight as f32)
+    }
+
+    /// Atlas image name of a tile, built as `<tileset name>_<local tile id>`.
+    pub fn tile_sprite(&self, gid: u32) -> Option<String> {
+        let gid = gid & FLIPPED_FLAGS_MASK;
+        if gid == 0 {
+            return None;
+        }
+        self.tilesets
+            .iter()
+            .filter(|tileset| tileset.firstgid <= gid)
+            .max_by_key(|tileset| tileset.firstgid)
+            .map(|tileset| format!("{}_{}", tileset.name, gid - tileset.firstgid))
+    }
+}
+
+/// Solid tiles of the map's collision layer, queried by the physics.
+#[derive(Debug, Default, Clone)]
+pub struct TileCollisionMap {
+    pub width: u32,
+    pub height: u32,
+    pub tile_size: Vector,
+    solid: Vec<bool>,
+    /// Whether any tile is solid, computed once on load since the physics asks every tick.
+    has_solid: bool,
+}
+
+impl TileCollisionMap {
+    pub fn new(width: u32, height: u32, tile_size: Vector, solid: Vec<bool>) -> TileCollisionMap {
+        let has_solid = solid.iter().any(|solid| *solid);
+        TileCollisionMap {
+            width,
+            height,
+            tile_size,
+            solid,
+            has_solid,
+        }
+    }
+
+    /// Whether the scene has no solid tiles to stand on.
+    pub fn is_empty(&self) -> bool {
+        !self.has_solid
+    }
+
+    pub fn is_solid(&self, column: i32, row: i32) -> bool {
+        if column < 0 || row < 0 || column >= self.width as i32 || row >= self.height as i32 {
+            return false;
+        }
+        self.solid
+            .get((row as u32 * self.width + column as u32) as usize)
+            .copied()
+            .unwrap_or(false)
+    }
+
+    /// Top of the highest solid tile in the column under `x`, clamped to the map.
+    pub fn surface(&self, x: f32) -> Option<f32> {
+        if self.width == 0 {
+            return None;
+        }
+        let column = self.column(x).max(0).min(self.width as i32 - 1);
+        (0..self.height as i32)
+            .find(|row| self.is_solid(column, *row))
+            .map(|row| row as f32 * self.tile_size.y)
+    }
+
+    pub fn tile_area(&self, column: i32, row: i32) -> Rectangle {
+        Rectangle::new(
+            Vector::new(
+                column as f32 * self.tile_size.x,
+                row as f32 * self.tile_size.y,
+            ),
+            self.tile_size,
+        )
+    }
+
+    /// Areas covered by each horizontal run of consecutive solid tiles.
+    pub fn solid_runs(&self) -> Vec<Rectangle> {
+        let mut runs = Vec::new();
+        for row in 0..self.height as i32 {
+            let mut start = None;
+            for column in 0..=self.width as i32 {
+                match (start, self.is_solid(column, row)) {
+                    (None, true) => start = Some(column),
+                    (Some(first), false) => {
+                        let area = self.tile_area(first, row);
+                        runs.push(Rectangle::new(
+                            area.pos,
+                            Vector::new((column - first) as f32 * self.tile_size.x, area.size.y),
+                        ));
+                        start = None;
+                    }
+                    _ => {}
+                }
+            }
+        }
+        runs
+    }
+
+    fn column(&self, x: f32) -> i32 {
+        (x / self.tile_size.x).floor() as i32
+    }
+
+    fn row(&self, y: f32) -> i32 {
+        (y / self.tile_size.y).floor() as i32
+    }
+
+    /// Pushes a body with the given half size out of the solid tiles it moved into.
+    /// Returns whether the body is standing on a solid tile.
+    pub fn resolve(
+        &self,
+        position: &mut Vector,
+        velocity: &mut Vector,
+        half_size: Vector,
+        time_step: f32,
+    ) -> bool {
+        if self.solid.is_empty() {
+            return false;
+        }
+        let mut grounded = false;
+        let previous = *position - *velocity * time_step;
+
+        let (left, right) = (
+            self.column(position.x - half_size.x + 1.0),
+            self.column(position.x + half_size.x - 1.0),
+        );
+        if velocity.y >= 0.0 {
+            let row = self.row(position.y + half_size.y);
+            let tile_top = row as f32 * self.tile_size.y;
+            if (left..=right).any(|column| self.is_solid(column, row))
+                && previous.y + half_size.y <= tile_top + 1.0
+            {
+                position.y = tile_top - half_size.y;
+                velocity.y = 0.0;
+                grounded = true;
+            }
+        } else {
+            let row = self.row(position.y - half_size.y);
+            let tile_bottom = (row + 1) as f32 * self.tile_size.y;
+            if (left..=right).any(|column| self.is_solid(column, row))
+                && previous.y - half_size.y >= tile_bottom - 1.0
+            {
+                position.y = tile_bottom + half_size.y;
+                velocity.y = 0.0;
+            }
+        }
+
+        let (top, bottom) = (
+            self.row(position.y - half_size.y + 1.0),
+            self.row(position.y + half_size.y - 1.0),
+        );
+        if velocity.x > 0.0 {
+            let column = self.column(position.x + half_size.x);
+            if (top..=bottom).any(|row| self.is_solid(column, row)) {
+                position.x = column as f32 * self.tile_size.x - half_size.x;
+            }
+        } else if velocity.x < 0.0 {
+            let column = self.column(position.x - half_size.x);
+            if (top..=bottom).any(|row| self.is_solid(column, row)) {
+                position.x = (column + 1) as f32 * self.tile_size.x + half_size.x;
+            }
+        }
+        grounded
+    }
+}
+
+/// Positions authored in the map's object layers that the scene applies later.
+#[derive(Debug, Default, Clone)]
+pub struct MapSpawns {
+    pub hero_start: Option<Vector>,
+    pub boss_position: Option<Vector>,
+}
+
+pub fn create_map(world: &mut World, map: &TiledMap, config: &TileMapConfig) -> MapSpawns {
+    let mut spawns = MapSpawns::default();
+    let tile_size = map.tile_size();
+    {
+        let mut bounds = world.write_resource::<WorldBounds>();
+        bounds.position = Vector::ZERO;
+        bounds.size = Vector::new(map.width as f32, map.height as f32).times(tile_size);
+    }
+
+    for layer in &map.layers {
+        match layer {
+            TiledLayer::TileLayer {
+                name,
+                width,
+                height,
+                data,
+                visible,
+            } => {
+                if data.len() != (*width * *height) as usize {
+                    log::warn!(
+                        "Tile layer '{}' has {} tiles instead of {}x{}",
+                        name,
+                        data.len(),
+                        width,
+                        height
+                    );
+                }
+                if *name == config.collision_layer {
+                    let tile_map = TileCollisionMap::new(
+                        *width,
+                        *height,
+                        tile_size,
+                        data.iter().map(|gid| *gid != 0).collect(),
+                    );
+                    create_walls(world, &tile_map, &config.wall_layer);
+                    world.add_resource(tile_map);
+                }
+                if !*visible {
+                    continue;
+                }
+                for (i, gid) in data.iter().enumerate() {
+                    if let Some(sprite) = map.tile_sprite(*gid) {
+                        let column = i as u32 % width;
+                        let row = i as u32 / width;
+                        create_tile(
+                            world,
+                            sprite,
+                            Vector::new(column as f32, row as f32).times(tile_size)
+                                + tile_size / 2.0,
+                        );
+                    }
+                }
+            }
+            TiledLayer::ObjectGroup { objects, .. } => {
+                for object in objects {
+                    create_map_object(world, object, &mut spawns);
+                }
+            }
+            TiledLayer::Other => {}
+        }
+    }
+    spawns
+}
+
+fn create_map_object(world: &mut World, object: &TiledObject, spawns: &mut MapSpawns) {
+    let position = object.center();
+    match object.object_type.as_str() {
+        "hero_start" => spawns.hero_start = Some(position),
+        "boss" => spawns.boss_position = Some(position),
+        "walker" => crate::enemy::create_enemy_at(world, EnemyType::Walker, position),
+        "shooter" => crate::enemy::create_enemy_at(world, EnemyType::Shooter, position),
+        "flyer" => crate::enemy::create_enemy_at(world, EnemyType::Flyer, position),
+        "potion" => {
+            let healing = world.read_resource::<PrefabsConfig>().healing.clone();
+            crate::healing::create_healing_potion(
+                world,
+                HealingConfig {
+                    position,
+                    velocity: Vector::ZERO,
+                    gravity_scale: None,
+                    lifetime_ms: None,
+                    ..healing
+                },
+            );
+        }
+        other => log::warn!("Unknown map object type '{}' ({})", other, object.name),
+    }
+}
+
+/// Collision-only entities over the solid tiles, so projectiles and other layers can hit them.
+/// Each horizontal run of solid tiles shares one collider.
+fn create_walls(world: &mut World, tile_map: &TileCollisionMap, layer: &CollisionLayerConfig) {
+    for area in tile_map.solid_runs() {
+        world
+            .create_entity()
+            .with(MapTile)
+            .with(Position {
+                position: area.center(),
+            })
+            .with(Collider {
+                hitboxes: crate::collider::body(area.size),
+            })
+            .with(CollisionLayer::new(layer))
+            .build();
+    }
+}
+
+fn create_tile(world: &mut World, sprite: String, position: Vector) -> Entity {
+    let sprite = intern_sprite(world, &sprite);
+    world
+        .create_entity()
+        .with(MapTile)
+        .with(Position { position })
+        .with(Render {
+            sprite,
+            layer: RenderLayer::Background,
+            z: 0.0,
+        })
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map(solid: &[u8]) -> TileCollisionMap {
+        TileCollisionMap::new(
+            3,
+            3,
+            Vector::new(32.0, 32.0),
+            solid.iter().map(|tile| *tile != 0).collect(),
+        )
+    }
+
+    #[test]
+    fn is_empty_without_solid_tiles() {
+        assert!(TileCollisionMap::default().is_empty());
+        assert!(map(&[0, 0, 0]).is_empty());
+        assert!(!map(&[0, 1, 0]).is_empty());
+    }
+
+    #[test]
+    fn short_data_is_not_solid() {
+        let map = map(&[0, 0, 1]);
+        assert!(map.is_solid(2, 0));
+        assert!(!map.is_solid(2, 2));
+        assert!(!map.is_solid(-1, 0));
+    }
+
+    #[test]
+    fn resolve_lands_on_floor() {
+        let map = map(&[0, 0, 0, 0, 0, 0, 1, 1, 1]);
+        let mut position = Vector::new(48.0, 60.0);
+        let mut velocity = Vector::new(0.0, 600.0);
+        let grounded = map.resolve(&mut position, &mut velocity, Vector::new(8.0, 8.0), 0.0167);
+        assert!(grounded);
+        assert_eq!(position.y, 56.0);
+        assert_eq!(velocity.y, 0.0);
+    }
+
+    #[test]
+    fn resolve_stops_at_wall() {
+        let map = map(&[0, 0, 1, 0, 0, 1, 0, 0, 1]);
+        let mut position = Vector::new(60.0, 48.0);
+        let mut velocity = Vector::new(100.0, 0.0);
+        let grounded = map.resolve(&mut position, &mut velocity, Vector::new(8.0, 8.0), 0.0167);
+        assert!(!grounded);
+        assert_eq!(position.x, 56.0);
+    }
+
+    #[test]
+    fn surface_is_the_highest_solid_tile() {
+        let map = map(&[0, 0, 0, 0, 0, 1, 1, 1, 1]);
+        assert_eq!(map.surface(16.0), Some(64.0));
+        assert_eq!(map.surface(80.0), Some(32.0));
+        assert_eq!(map.surface(500.0), Some(32.0));
+        assert_eq!(TileCollisionMap::default().surface(16.0), None);
+    }
+
+    #[test]
+    fn solid_runs_merge_adjacent_tiles() {
+        let map = map(&[1, 1, 0, 0, 0, 0, 1, 0, 1]);
+        assert_eq!(
+            map.solid_runs(),
+            vec![
+                Rectangle::new((0, 0), (64, 32)),
+                Rectangle::new((0, 64), (32, 32)),
+                Rectangle::new((64, 64), (32, 32)),
+            ]
+        );
+        assert!(TileCollisionMap::default().solid_runs().is_empty());
+    }
+
+    #[test]
+    fn empty_map_does_nothing() {
+        let map = TileCollisionMap::default();
+        let mut position = Vector::new(10.0, 10.0);
+        let mut velocity = Vector::new(0.0, 100.0);
+        assert!(!map.resolve(&mut position, &mut velocity, Vector::new(8.0, 8.0), 0.0167));
+        assert_eq!(position, Vector::new(10.0, 10.0));
+    }
+}
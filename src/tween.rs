@@ -0,0 +1,454 @@
+use std::{f32::consts::PI, time::Duration};
+
+use crate::component::Tween;
+use quicksilver::geom::Vector;
+use serde::{Deserialize, Serialize};
+use specs::Entity;
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Copy, Clone)]
+pub enum Easing {
+    Linear,
+    QuadIn,
+    QuadOut,
+    QuadInOut,
+    CubicIn,
+    CubicOut,
+    CubicInOut,
+    SineIn,
+    SineOut,
+    SineInOut,
+    BackIn,
+    BackOut,
+    ElasticOut,
+    BounceOut,
+}
+
+impl Default for Easing {
+    fn default() -> Easing {
+        Easing::Linear
+    }
+}
+
+impl Easing {
+    /// Maps the linear progress `t`, from 0.0 to 1.0, onto the curve.
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.max(0.0).min(1.0);
+        let back = 1.70158;
+        match self {
+            Easing::Linear => t,
+            Easing::QuadIn => t * t,
+            Easing::QuadOut => t * (2.0 - t),
+            Easing::QuadInOut => {
+                if t < 0.5 {
+                    2.0 * t * t
+                } else {
+                    -1.0 + (4.0 - 2.0 * t) * t
+                }
+            }
+            Easing::CubicIn => t * t * t,
+            Easing::CubicOut => (t - 1.0).powi(3) + 1.0,
+            Easing::CubicInOut => {
+                if t < 0.5 {
+                    4.0 * t * t * t
+                } else {
+                    (t - 1.0) * (2.0 * t - 2.0) * (2.0 * t - 2.0) + 1.0
+                }
+            }
+            Easing::SineIn => 1.0 - (t * PI / 2.0).cos(),
+            Easing::SineOut => (t * PI / 2.0).sin(),
+            Easing::SineInOut => -((PI * t).cos() - 1.0) / 2.0,
+            Easing::BackIn => t * t * ((back + 1.0) * t - back),
+            Easing::BackOut => {
+                let t = t - 1.0;
+                t * t * ((back + 1.0) * t + back) + 1.0
+            }
+            Easing::ElasticOut => {
+                if t == 0.0 || t == 1.0 {
+                    t
+                } else {
+                    2f32.powf(-10.0 * t) * ((t * 10.0 - 0.75) * (2.0 * PI / 3.0)).sin() + 1.0
+                }
+            }
+            Easing::BounceOut => {
+                let (n, d) = (7.5625, 2.75);
+                if t < 1.0 / d {
+                    n * t * t
+                } else if t < 2.0 / d {
+                    let t = t - 1.5 / d;
+                    n * t * t + 0.75
+                } else if t < 2.5 / d {
+                    let t = t - 2.25 / d;
+                    n * t * t + 0.9375
+                } else {
+                    let t = t - 2.625 / d;
+                    n * t * t + 0.984_375
+                }
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone)]
+pub enum TweenProperty {
+    /// Moves the entity between two world positions.
+    Position {
+        from: Vector,
+        to: Vector,
+    },
+    /// Moves the entity by `by` from where the step starts, on top of its velocity.
+    Move {
+        by: Vector,
+    },
+    /// Draws the sprite away from the entity position, leaving its collider in place.
+    Offset {
+        from: Vector,
+        to: Vector,
+    },
+    Scale {
+        from: f32,
+        to: f32,
+    },
+    /// Degrees.
+    Rotation {
+        from: f32,
+        to: f32,
+    },
+    Alpha {
+        from: f32,
+        to: f32,
+    },
+}
+
+/// Value of a property at some point of its step. `Move` has none, its steps are applied as
+/// displacements.
+#[derive(Debug, Copy, Clone)]
+pub enum TweenValue {
+    Position(Vector),
+    Offset(Vector),
+    Scale(f32),
+    Rotation(f32),
+    Alpha(f32),
+}
+
+impl TweenProperty {
+    pub fn sample(&self, t: f32) -> Option<TweenValue> {
+        let lerp = |from: f32, to: f32| from + (to - from) * t;
+        let lerp_vector = |from: Vector, to: Vector| from + (to - from) * t;
+        Some(match *self {
+            TweenProperty::Position { from, to } => TweenValue::Position(lerp_vector(from, to)),
+            TweenProperty::Move { .. } => return None,
+            TweenProperty::Offset { from, to } => TweenValue::Offset(lerp_vector(from, to)),
+            TweenProperty::Scale { from, to } => TweenValue::Scale(lerp(from, to)),
+            TweenProperty::Rotation { from, to } => TweenValue::Rotation(lerp(from, to)),
+            TweenProperty::Alpha { from, to } => TweenValue::Alpha(lerp(from, to)),
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone)]
+pub struct TweenStep {
+    pub property: TweenProperty,
+    pub duration_ms: u64,
+    #[serde(default)]
+    pub easing: Easing,
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Copy, Clone)]
+pub enum TweenRepeat {
+    Once,
+    /// Restarts from the first step.
+    Loop,
+    /// Plays the steps forward, then backward.
+    Yoyo,
+}
+
+/// What happens to the entity once the tween is done.
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Copy, Clone)]
+pub enum TweenCompletion {
+    /// Removes the `Tween`, leaving the last values in place.
+    Remove,
+    Keep,
+    /// Deletes the entity.
+    Delete,
+}
+
+/// Steps played one after the other.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct TweenConfig {
+    pub steps: Vec<TweenStep>,
+    pub repeat: TweenRepeat,
+    /// Cycles played before finishing, forever when `None`. Each way of a yoyo is a cycle.
+    pub repeat_count: Option<u32>,
+    pub on_complete: TweenCompletion,
+}
+
+impl Default for TweenConfig {
+    fn default() -> TweenConfig {
+        TweenConfig {
+            steps: Vec::new(),
+            repeat: TweenRepeat::Once,
+            repeat_count: None,
+            on_complete: TweenCompletion::Remove,
+        }
+    }
+}
+
+impl TweenConfig {
+    pub fn once(property: TweenProperty, duration_ms: u64, easing: Easing) -> TweenConfig {
+        TweenConfig {
+            steps: vec![TweenStep {
+                property,
+                duration_ms,
+                easing,
+            }],
+            ..TweenConfig::default()
+        }
+    }
+
+    /// Sum of the `Move` steps, the distance covered by one forward pass.
+    pub fn displacement(&self) -> Vector {
+        self.steps
+            .iter()
+            .fold(Vector::ZERO, |total, step| match step.property {
+                TweenProperty::Move { by } => total + by,
+                _ => total,
+            })
+    }
+
+    /// Endless up and down motion, for pickups.
+    pub fn bob(height: f32, duration_ms: u64) -> TweenConfig {
+        TweenConfig {
+            repeat: TweenRepeat::Yoyo,
+            ..TweenConfig::once(
+                TweenProperty::Offset {
+                    from: Vector::ZERO,
+                    to: Vector::new(0.0, -height),
+                },
+                duration_ms,
+                Easing::SineInOut,
+            )
+        }
+    }
+}
+
+impl Tween {
+    pub fn new(config: TweenConfig) -> Tween {
+        Tween {
+            config,
+            step: 0,
+            elapsed: Duration::from_millis(0),
+            forward: true,
+            cycles: 0,
+            applied: 0.0,
+            finished: false,
+        }
+    }
+
+    fn duration(&self) -> Duration {
+        self.config
+            .steps
+            .get(self.step)
+            .map_or(Duration::from_millis(0), |step| {
+                Duration::from_millis(step.duration_ms)
+            })
+    }
+
+    /// Eased progress of the current step, going back to 0.0 on the way back of a yoyo.
+    pub fn progress(&self) -> f32 {
+        let step = match self.config.steps.get(self.step) {
+            Some(step) => step,
+            None => return 1.0,
+        };
+        let duration = self.duration().as_micros() as f32;
+        let t = if duration > 0.0 {
+            (self.elapsed.as_micros() as f32 / duration).min(1.0)
+        } else {
+            1.0
+        };
+        step.easing.apply(if self.forward { t } else { 1.0 - t })
+    }
+
+    pub fn value(&self) -> Option<TweenValue> {
+        self.config
+            .steps
+            .get(self.step)
+            .and_then(|step| step.property.sample(self.progress()))
+    }
+
+    /// Moves the tween forward and returns the displacement made by `Move` steps.
+    pub fn advance(&mut self, time_step: Duration) -> Vector {
+        let mut moved = Vector::ZERO;
+        if self.finished || self.config.steps.is_empty() {
+            self.finished = true;
+            return moved;
+        }
+        self.elapsed += time_step;
+        // Bounded so looping steps without duration can't spin forever.
+        for _ in 0..=self.config.steps.len() * 2 {
+            let duration = self.duration();
+            if self.elapsed < duration {
+                break;
+            }
+            let carry = self.elapsed - duration;
+            self.elapsed = duration;
+            moved += self.move_delta();
+            if !self.next_step() {
+                self.finished = true;
+                return moved;
+            }
+            self.elapsed = carry;
+            self.applied = if self.forward { 0.0 } else { 1.0 };
+        }
+        moved + self.move_delta()
+    }
+
+    fn move_delta(&mut self) -> Vector {
+        let progress = self.progress();
+        let delta = match self.config.steps[self.step].property {
+            TweenProperty::Move { by } => by * (progress - self.applied),
+            _ => Vector::ZERO,
+        };
+        self.applied = progress;
+        delta
+    }
+
+    fn next_step(&mut self) -> bool {
+        if self.forward && self.step + 1 < self.config.steps.len() {
+            self.step += 1;
+            return true;
+        }
+        if !self.forward && self.step > 0 {
+            self.step -= 1;
+            return true;
+        }
+        self.cycles += 1;
+        match self.config.repeat {
+            TweenRepeat::Once => false,
+            _ if self
+                .config
+                .repeat_count
+                .map_or(false, |count| self.cycles >= count) =>
+            {
+                false
+            }
+            TweenRepeat::Loop => {
+                self.step = 0;
+                true
+            }
+            TweenRepeat::Yoyo => {
+                self.forward = !self.forward;
+                true
+            }
+        }
+    }
+}
+
+/// Entities whose tween finished during the last update.
+#[derive(Debug, Default, Clone)]
+pub struct TweenEvents {
+    pub finished: Vec<Entity>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: [Easing; 14] = [
+        Easing::Linear,
+        Easing::QuadIn,
+        Easing::QuadOut,
+        Easing::QuadInOut,
+        Easing::CubicIn,
+        Easing::CubicOut,
+        Easing::CubicInOut,
+        Easing::SineIn,
+        Easing::SineOut,
+        Easing::SineInOut,
+        Easing::BackIn,
+        Easing::BackOut,
+        Easing::ElasticOut,
+        Easing::BounceOut,
+    ];
+
+    #[test]
+    fn easings_start_at_zero_and_end_at_one() {
+        for easing in ALL.iter() {
+            assert!(easing.apply(0.0).abs() < 1e-5, "{:?}", easing);
+            assert!((easing.apply(1.0) - 1.0).abs() < 1e-5, "{:?}", easing);
+        }
+    }
+
+    #[test]
+    fn progress_is_clamped() {
+        for easing in ALL.iter() {
+            assert_eq!(easing.apply(-1.0), easing.apply(0.0), "{:?}", easing);
+            assert_eq!(easing.apply(2.0), easing.apply(1.0), "{:?}", easing);
+        }
+    }
+
+    fn move_by(x: f32, duration_ms: u64) -> TweenConfig {
+        TweenConfig::once(
+            TweenProperty::Move {
+                by: Vector::new(x, 0.0),
+            },
+            duration_ms,
+            Easing::Linear,
+        )
+    }
+
+    #[test]
+    fn tween_moves_by_the_whole_step_and_finishes() {
+        let mut tween = Tween::new(move_by(100.0, 100));
+        let mut moved = Vector::ZERO;
+        for _ in 0..3 {
+            moved += tween.advance(Duration::from_millis(40));
+        }
+        assert_eq!(moved, Vector::new(100.0, 0.0));
+        assert!(tween.finished);
+        assert_eq!(tween.advance(Duration::from_millis(40)), Vector::ZERO);
+    }
+
+    #[test]
+    fn tween_carries_time_into_the_next_step() {
+        let mut config = move_by(10.0, 100);
+        config.steps.push(config.steps[0]);
+        let mut tween = Tween::new(config);
+        let moved = tween.advance(Duration::from_millis(150));
+        assert_eq!(tween.step, 1);
+        assert_eq!(tween.elapsed, Duration::from_millis(50));
+        assert_eq!(moved, Vector::new(15.0, 0.0));
+    }
+
+    #[test]
+    fn yoyo_tween_comes_back_and_stops_after_its_cycles() {
+        let mut tween = Tween::new(TweenConfig {
+            repeat: TweenRepeat::Yoyo,
+            repeat_count: Some(2),
+            ..move_by(10.0, 100)
+        });
+        assert_eq!(
+            tween.advance(Duration::from_millis(100)),
+            Vector::new(10.0, 0.0)
+        );
+        assert!(!tween.forward);
+        assert!(!tween.finished);
+        assert_eq!(
+            tween.advance(Duration::from_millis(100)),
+            Vector::new(-10.0, 0.0)
+        );
+        assert!(tween.finished);
+    }
+
+    #[test]
+    fn curves_at_the_midpoint() {
+        assert_eq!(Easing::Linear.apply(0.5), 0.5);
+        assert_eq!(Easing::QuadIn.apply(0.5), 0.25);
+        assert_eq!(Easing::QuadOut.apply(0.5), 0.75);
+        assert_eq!(Easing::QuadInOut.apply(0.5), 0.5);
+        assert_eq!(Easing::CubicIn.apply(0.5), 0.125);
+        assert!((Easing::SineInOut.apply(0.5) - 0.5).abs() < 1e-5);
+        assert!(Easing::BackIn.apply(0.2) < 0.0);
+        assert!(Easing::BackOut.apply(0.8) > 1.0);
+    }
+}
@@ -0,0 +1,705 @@
+use std::collections::HashMap;
+
+use quicksilver::{
+    geom::{Rectangle, Shape, Vector},
+    graphics::{Background::Col, Background::Img, Color, Font, FontStyle, Image},
+    input::{ButtonState, GamepadButton, Key},
+    lifecycle::{Event, Window},
+    Result,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    label::LabelCache,
+    localization::Localization,
+    sprite::{SpriteHandle, SpriteImages, SpriteTable},
+};
+
+pub type Rgba = (f32, f32, f32, f32);
+
+fn color((r, g, b, a): Rgba) -> Color {
+    Color { r, g, b, a }
+}
+
+/// Sprite drawn with its corners kept at their size and the rest stretched to fit.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NineSlice {
+    pub sprite: String,
+    /// Size of the corners in the sprite, in pixels.
+    pub border: f32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum UiAction {
+    /// Leaves the menus and starts playing.
+    Start,
+    /// Shows another screen on top of this one.
+    Open(String),
+    /// Goes back to the previous screen.
+    Back,
+    Quit,
+    /// Handled by the game.
+    Custom(String),
+}
+
+/// Texts are string keys, looked up in the `Localization`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum WidgetKind {
+    Panel {
+        color: Option<Rgba>,
+        nine_slice: Option<NineSlice>,
+    },
+    Label {
+        text: String,
+        font_size: f32,
+    },
+    Button {
+        text: String,
+        action: UiAction,
+    },
+    Slider {
+        text: String,
+        min: f32,
+        max: f32,
+        step: f32,
+        value: f32,
+    },
+    Toggle {
+        text: String,
+        value: bool,
+    },
+    List {
+        text: String,
+        items: Vec<String>,
+        selected: usize,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WidgetConfig {
+    /// Name the changes of this widget are reported with.
+    #[serde(default)]
+    pub id: String,
+    pub kind: WidgetKind,
+    /// Center of the widget. Widgets without one are stacked down from the screen origin.
+    #[serde(default)]
+    pub position: Option<Vector>,
+    pub size: Vector,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ScreenConfig {
+    pub widgets: Vec<WidgetConfig>,
+    pub origin: Vector,
+    pub spacing: f32,
+}
+
+impl Default for ScreenConfig {
+    fn default() -> ScreenConfig {
+        ScreenConfig {
+            widgets: Vec::new(),
+            origin: Vector::new(400.0, 200.0),
+            spacing: 16.0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct UiConfig {
+    pub screens: HashMap<String, ScreenConfig>,
+    /// Screen shown before the game starts.
+    pub title_screen: String,
+    /// Screen shown with the results of a run.
+    pub game_over_screen: String,
+    pub text_color: Rgba,
+    pub focus_color: Rgba,
+    pub widget_color: Rgba,
+    pub font_size: f32,
+}
+
+impl Default for UiConfig {
+    fn default() -> UiConfig {
+        UiConfig {
+            screens: default_screens(),
+            title_screen: "title".to_string(),
+            game_over_screen: "game_over".to_string(),
+            text_color: (0.0, 0.0, 0.0, 1.0),
+            focus_color: (1.0, 0.8, 0.3, 1.0),
+            widget_color: (0.9, 0.9, 0.9, 0.9),
+            font_size: 36.0,
+        }
+    }
+}
+
+fn button(text: &str, action: UiAction) -> WidgetConfig {
+    WidgetConfig {
+        id: String::new(),
+        kind: WidgetKind::Button {
+            text: text.to_string(),
+            action,
+        },
+        position: None,
+        size: Vector::new(320.0, 56.0),
+    }
+}
+
+/// Items of the default game speed list and their time scales.
+pub const GAME_SPEEDS: [(&str, f32); 3] = [
+    ("menu.speed_slow", 0.5),
+    ("menu.speed_normal", 1.0),
+    ("menu.speed_fast", 1.5),
+];
+
+pub fn default_screens() -> HashMap<String, ScreenConfig> {
+    let title = ScreenConfig {
+        widgets: vec![
+            button("menu.start_game", UiAction::Start),
+            button("menu.options", UiAction::Open("options".to_string())),
+            button("menu.quit", UiAction::Quit),
+        ],
+        origin: Vector::new(400.0, 260.0),
+        ..ScreenConfig::default()
+    };
+    let options = ScreenConfig {
+        widgets: vec![
+            WidgetConfig {
+                id: "camera_effects".to_string(),
+                kind: WidgetKind::Toggle {
+                    text: "menu.camera_effects".to_string(),
+                    value: true,
+                },
+                position: None,
+                size: Vector::new(420.0, 56.0),
+            },
+            WidgetConfig {
+                id: "music_volume".to_string(),
+                kind: WidgetKind::Slider {
+                    text: "menu.music_volume".to_string(),
+                    min: 0.0,
+                    max: 1.0,
+                    step: 0.1,
+                    value: 0.75,
+                },
+                position: None,
+                size: Vector::new(420.0, 56.0),
+            },
+            WidgetConfig {
+                id: "game_speed".to_string(),
+                kind: WidgetKind::List {
+                    text: "menu.game_speed".to_string(),
+                    items: GAME_SPEEDS.iter().map(|(key, _)| key.to_string()).collect(),
+                    selected: 1,
+                },
+                position: None,
+                size: Vector::new(420.0, 56.0),
+            },
+            button("menu.back", UiAction::Back),
+        ],
+        origin: Vector::new(400.0, 260.0),
+        ..ScreenConfig::default()
+    };
+    let game_over = ScreenConfig {
+        widgets: vec![
+            button("menu.retry", UiAction::Custom("retry".to_string())),
+            button("menu.title", UiAction::Custom("title".to_string())),
+            button("menu.quit", UiAction::Quit),
+        ],
+        origin: Vector::new(400.0, 280.0),
+        ..ScreenConfig::default()
+    };
+    [
+        ("title", title),
+        ("options", options),
+        ("game_over", game_over),
+    ]
+    .iter()
+    .cloned()
+    .map(|(name, screen)| (name.to_string(), screen))
+    .collect()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum UiValue {
+    Number(f32),
+    Bool(bool),
+    Index(usize),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum UiEvent {
+    Action(UiAction),
+    Changed(String, UiValue),
+}
+
+struct Widget {
+    config: WidgetConfig,
+    area: Rectangle,
+    /// Interned when the screen opens.
+    sprite: Option<SpriteHandle>,
+}
+
+impl Widget {
+    fn focusable(&self) -> bool {
+        match self.config.kind {
+            WidgetKind::Panel { .. } | WidgetKind::Label { .. } => false,
+            _ => true,
+        }
+    }
+
+    /// Moves a slider, toggle or list by one step.
+    fn adjust(&mut self, direction: i32) -> Option<UiValue> {
+        match &mut self.config.kind {
+            WidgetKind::Slider {
+                min,
+                max,
+                step,
+                value,
+                ..
+            } => {
+                *value = (*value + *step * direction as f32).max(*min).min(*max);
+                Some(UiValue::Number(*value))
+            }
+            WidgetKind::Toggle { value, .. } => {
+                *value = !*value;
+                Some(UiValue::Bool(*value))
+            }
+            WidgetKind::List {
+                items, selected, ..
+            } if !items.is_empty() => {
+                let len = items.len() as i32;
+                *selected = ((*selected as i32 + direction + len) % len) as usize;
+                Some(UiValue::Index(*selected))
+            }
+            _ => None,
+        }
+    }
+}
+
+struct Screen {
+    widgets: Vec<Widget>,
+    focus: Option<usize>,
+}
+
+enum Input {
+    Up,
+    Down,
+    Left,
+    Right,
+    Confirm,
+    Cancel,
+}
+
+fn input(event: &Event) -> Option<Input> {
+    match event {
+        Event::Key(Key::Up, ButtonState::Pressed)
+        | Event::GamepadButton(_, GamepadButton::DpadUp, ButtonState::Pressed) => Some(Input::Up),
+        Event::Key(Key::Down, ButtonState::Pressed)
+        | Event::GamepadButton(_, GamepadButton::DpadDown, ButtonState::Pressed) => {
+            Some(Input::Down)
+        }
+        Event::Key(Key::Left, ButtonState::Pressed)
+        | Event::GamepadButton(_, GamepadButton::DpadLeft, ButtonState::Pressed) => {
+            Some(Input::Left)
+        }
+        Event::Key(Key::Right, ButtonState::Pressed)
+        | Event::GamepadButton(_, GamepadButton::DpadRight, ButtonState::Pressed) => {
+            Some(Input::Right)
+        }
+        Event::Key(Key::Return, ButtonState::Pressed)
+        | Event::GamepadButton(_, GamepadButton::FaceDown, ButtonState::Pressed)
+        | Event::GamepadButton(_, GamepadButton::Start, ButtonState::Pressed) => {
+            Some(Input::Confirm)
+        }
+        Event::Key(Key::Escape, ButtonState::Pressed)
+        | Event::GamepadButton(_, GamepadButton::FaceRight, ButtonState::Pressed) => {
+            Some(Input::Cancel)
+        }
+        _ => None,
+    }
+}
+
+/// Stack of menu screens built from the config, the top one taking the input.
+pub struct Ui {
+    config: UiConfig,
+    stack: Vec<Screen>,
+}
+
+impl Ui {
+    pub fn new(config: UiConfig) -> Ui {
+        Ui {
+            config,
+            stack: Vec::new(),
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        !self.stack.is_empty()
+    }
+
+    pub fn close(&mut self) {
+        self.stack.clear();
+    }
+
+    pub fn open(&mut self, name: &str, sprites: &mut SpriteTable) {
+        let config = match self.config.screens.get(name) {
+            Some(config) => config,
+            None => {
+                log::warn!("There is no UI screen called '{}'", name);
+                return;
+            }
+        };
+        let mut next_top = config.origin.y;
+        let widgets: Vec<Widget> = config
+            .widgets
+            .iter()
+            .map(|widget| {
+                let center = widget.position.unwrap_or_else(|| {
+                    let center = Vector::new(config.origin.x, next_top + widget.size.y / 2.0);
+                    next_top += widget.size.y + config.spacing;
+                    center
+                });
+                let sprite = match &widget.kind {
+                    WidgetKind::Panel {
+                        nine_slice: Some(nine_slice),
+                        ..
+                    } => Some(sprites.intern(&nine_slice.sprite)),
+                    _ => None,
+                };
+                Widget {
+                    config: widget.clone(),
+                    area: Rectangle::new(center - widget.size / 2.0, widget.size),
+                    sprite,
+                }
+            })
+            .collect();
+        let focus = widgets.iter().position(Widget::focusable);
+        self.stack.push(Screen { widgets, focus });
+    }
+
+    /// Current value of the widget called `id` on the top screen.
+    pub fn value(&self, id: &str) -> Option<UiValue> {
+        let widget = self
+            .stack
+            .last()?
+            .widgets
+            .iter()
+            .find(|widget| widget.config.id == id)?;
+        match &widget.config.kind {
+            WidgetKind::Slider { value, .. } => Some(UiValue::Number(*value)),
+            WidgetKind::Toggle { value, .. } => Some(UiValue::Bool(*value)),
+            WidgetKind::List { selected, .. } => Some(UiValue::Index(*selected)),
+            _ => None,
+        }
+    }
+
+    /// Sets the value of the widgets called `id`, on open screens and on the ones opened later.
+    pub fn set_value(&mut self, id: &str, value: UiValue) {
+        let configs = self
+            .config
+            .screens
+            .values_mut()
+            .flat_map(|screen| screen.widgets.iter_mut());
+        let open = self
+            .stack
+            .iter_mut()
+            .flat_map(|screen| screen.widgets.iter_mut())
+            .map(|widget| &mut widget.config);
+        for widget in configs.chain(open).filter(|widget| widget.id == id) {
+            match (&mut widget.kind, &value) {
+                (WidgetKind::Slider { value: current, .. }, UiValue::Number(value)) => {
+                    *current = *value
+                }
+                (WidgetKind::Toggle { value: current, .. }, UiValue::Bool(value)) => {
+                    *current = *value
+                }
+                (WidgetKind::List { selected, .. }, UiValue::Index(value)) => *selected = *value,
+                _ => log::warn!("Widget '{}' can't take {:?}", id, value),
+            }
+        }
+    }
+
+    /// Handles navigation, returning what the game has to act upon. `Open` and `Back` are
+    /// handled here.
+    pub fn event(&mut self, event: &Event, sprites: &mut SpriteTable) -> Option<UiEvent> {
+        let input = input(event)?;
+        let screen = self.stack.last_mut()?;
+        let ui_event = match input {
+            Input::Up | Input::Down => {
+                let step = if let Input::Up = input { -1 } else { 1 };
+                screen.focus = next_focus(&screen.widgets, screen.focus, step);
+                None
+            }
+            Input::Left | Input::Right => {
+                let direction = if let Input::Left = input { -1 } else { 1 };
+                let widget = &mut screen.widgets[screen.focus?];
+                let value = widget.adjust(direction)?;
+                Some(UiEvent::Changed(widget.config.id.clone(), value))
+            }
+            Input::Confirm => {
+                let widget = &mut screen.widgets[screen.focus?];
+                match &widget.config.kind {
+                    WidgetKind::Button { action, .. } => Some(UiEvent::Action(action.clone())),
+                    WidgetKind::Toggle { .. } | WidgetKind::List { .. } => {
+                        let value = widget.adjust(1)?;
+                        Some(UiEvent::Changed(widget.config.id.clone(), value))
+                    }
+                    _ => None,
+                }
+            }
+            Input::Cancel => Some(UiEvent::Action(UiAction::Back)),
+        };
+        match ui_event {
+            Some(UiEvent::Action(UiAction::Open(name))) => {
+                self.open(&name, sprites);
+                None
+            }
+            // The first screen can't be left, going back from it is up to the game.
+            Some(UiEvent::Action(UiAction::Back)) if self.stack.len() > 1 => {
+                self.stack.pop();
+                None
+            }
+            ui_event => ui_event,
+        }
+    }
+
+    /// Draws the top screen, in screen space.
+    pub fn draw(
+        &self,
+        window: &mut Window,
+        font: &mut Font,
+        cache: &mut LabelCache,
+        localization: &Localization,
+        images: &SpriteImages,
+    ) -> Result<()> {
+        let screen = match self.stack.last() {
+            Some(screen) => screen,
+            None => return Ok(()),
+        };
+        let text_color = color(self.config.text_color);
+        let mut text = Text {
+            font,
+            cache,
+            localization,
+            color: text_color,
+        };
+        for (index, widget) in screen.widgets.iter().enumerate() {
+            let area = widget.area;
+            let background = if screen.focus == Some(index) {
+                color(self.config.focus_color)
+            } else {
+                color(self.config.widget_color)
+            };
+            let font_size = self.config.font_size;
+            match &widget.config.kind {
+                WidgetKind::Panel {
+                    color: fill,
+                    nine_slice,
+                } => {
+                    if let Some(fill) = fill {
+                        window.draw(&area, Col(color(*fill)));
+                    }
+                    let image = widget.sprite.and_then(|sprite| images.get(sprite));
+                    if let (Some(image), Some(nine_slice)) = (image, nine_slice) {
+                        draw_nine_slice(window, image, area, nine_slice.border);
+                    }
+                }
+                WidgetKind::Label {
+                    text: key,
+                    font_size,
+                } => {
+                    text.draw(window, key, *font_size, area.center())?;
+                }
+                WidgetKind::Button { text: key, .. } => {
+                    window.draw(&area, Col(background));
+                    text.draw(window, key, font_size, area.center())?;
+                }
+                WidgetKind::Slider {
+                    text: key,
+                    min,
+                    max,
+                    value,
+                    ..
+                } => {
+                    window.draw(&area, Col(background));
+                    let label = Vector::new(area.x() + area.width() * 0.3, area.center().y);
+                    text.draw(window, key, font_size, label)?;
+                    let track = Rectangle::new(
+                        (area.x() + area.width() * 0.6, area.center().y - 4.0),
+                        (area.width() * 0.35, 8.0),
+                    );
+                    let fraction = if max > min {
+                        (value - min) / (max - min)
+                    } else {
+                        0.0
+                    };
+                    window.draw(&track, Col(Color::WHITE));
+                    window.draw(
+                        &Rectangle::new(track.pos, (track.width() * fraction, track.height())),
+                        Col(text_color),
+                    );
+                }
+                WidgetKind::Toggle { text: key, value } => {
+                    window.draw(&area, Col(background));
+                    let label = Vector::new(area.x() + area.width() * 0.4, area.center().y);
+                    text.draw(window, key, font_size, label)?;
+                    let check = Rectangle::new(
+                        (area.x() + area.width() - 44.0, area.center().y - 12.0),
+                        (24.0, 24.0),
+                    );
+                    window.draw(&check, Col(Color::WHITE));
+                    if *value {
+                        window.draw(
+                            &Rectangle::new(check.pos + Vector::new(5.0, 5.0), (14.0, 14.0)),
+                            Col(text_color),
+                        );
+                    }
+                }
+                WidgetKind::List {
+                    text: key,
+                    items,
+                    selected,
+                } => {
+                    window.draw(&area, Col(background));
+                    let label = Vector::new(area.x() + area.width() * 0.3, area.center().y);
+                    text.draw(window, key, font_size, label)?;
+                    if let Some(item) = items.get(*selected) {
+                        let item_center =
+                            Vector::new(area.x() + area.width() * 0.75, area.center().y);
+                        text.draw(window, item, font_size, item_center)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Draws strings by key, through the label cache.
+struct Text<'a> {
+    font: &'a mut Font,
+    cache: &'a mut LabelCache,
+    localization: &'a Localization,
+    color: Color,
+}
+
+impl<'a> Text<'a> {
+    fn draw(&mut self, window: &mut Window, key: &str, size: f32, center: Vector) -> Result<()> {
+        let style = FontStyle::new(size, self.color);
+        let text = self.localization.get(key);
+        let font = &mut self.font;
+        let image = self
+            .cache
+            .get_or_render(text, &style, || font.render(text, &style))?;
+        window.draw(&image.area().with_center(center), Img(image));
+        Ok(())
+    }
+}
+
+fn next_focus(widgets: &[Widget], focus: Option<usize>, step: i32) -> Option<usize> {
+    let len = widgets.len() as i32;
+    let start = focus.map_or(-step, |focus| focus as i32);
+    (1..=len)
+        .map(|offset| ((start + offset * step) % len + len) % len)
+        .map(|index| index as usize)
+        .find(|index| widgets[*index].focusable())
+        .or(focus)
+}
+
+/// Draws the 9 parts of `image` over `area`, `border` being the size of the corners. Corners
+/// shrink to fit areas smaller than two borders.
+fn draw_nine_slice(window: &mut Window, image: &Image, area: Rectangle, border: f32) {
+    let source = image.area();
+    let (source_x, source_y) = nine_slice_cuts(source, border);
+    let (target_x, target_y) = nine_slice_cuts(area, border);
+    for row in 0..3 {
+        for column in 0..3 {
+            let part = Rectangle::new(
+                (source_x[column], source_y[row]),
+                (
+                    source_x[column + 1] - source_x[column],
+                    source_y[row + 1] - source_y[row],
+                ),
+            );
+            let target = Rectangle::new(
+                area.pos + Vector::new(target_x[column], target_y[row]),
+                (
+                    target_x[column + 1] - target_x[column],
+                    target_y[row + 1] - target_y[row],
+                ),
+            );
+            window.draw(&target, Img(&image.subimage(part)));
+        }
+    }
+}
+
+/// Horizontal and vertical cuts between the 9 parts, the border clamped to half the area.
+fn nine_slice_cuts(area: Rectangle, border: f32) -> ([f32; 4], [f32; 4]) {
+    let border = border.max(0.0).min(area.width().min(area.height()) / 2.0);
+    let cuts = |size: f32| [0.0, border, size - border, size];
+    (cuts(area.width()), cuts(area.height()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn widget(kind: WidgetKind) -> Widget {
+        Widget {
+            config: WidgetConfig {
+                id: String::new(),
+                kind,
+                position: None,
+                size: Vector::new(100.0, 20.0),
+            },
+            area: Rectangle::new_sized((100.0, 20.0)),
+            sprite: None,
+        }
+    }
+
+    fn label() -> Widget {
+        widget(WidgetKind::Label {
+            text: String::new(),
+            font_size: 12.0,
+        })
+    }
+
+    fn toggle() -> Widget {
+        widget(WidgetKind::Toggle {
+            text: String::new(),
+            value: false,
+        })
+    }
+
+    #[test]
+    fn focus_skips_widgets_that_cant_take_it() {
+        let widgets = [label(), toggle(), label(), toggle()];
+        assert_eq!(next_focus(&widgets, None, 1), Some(1));
+        assert_eq!(next_focus(&widgets, Some(1), 1), Some(3));
+        assert_eq!(next_focus(&widgets, Some(3), -1), Some(1));
+    }
+
+    #[test]
+    fn focus_wraps_around() {
+        let widgets = [toggle(), label(), toggle()];
+        assert_eq!(next_focus(&widgets, Some(2), 1), Some(0));
+        assert_eq!(next_focus(&widgets, Some(0), -1), Some(2));
+        assert_eq!(next_focus(&widgets, None, -1), Some(0));
+    }
+
+    #[test]
+    fn focus_stays_without_other_focusable_widgets() {
+        assert_eq!(next_focus(&[label(), toggle()], Some(1), 1), Some(1));
+        assert_eq!(next_focus(&[label(), label()], None, 1), None);
+    }
+
+    #[test]
+    fn nine_slice_border_is_clamped_to_half_the_area() {
+        let (x, y) = nine_slice_cuts(Rectangle::new_sized((10.0, 40.0)), 8.0);
+        assert_eq!(x, [0.0, 5.0, 5.0, 10.0]);
+        assert_eq!(y, [0.0, 5.0, 35.0, 40.0]);
+        let (x, _) = nine_slice_cuts(Rectangle::new_sized((40.0, 40.0)), 8.0);
+        assert_eq!(x, [0.0, 8.0, 32.0, 40.0]);
+    }
+}